@@ -0,0 +1,23 @@
+use rusty_web::server_async::{AsyncPath, AsyncPaths, AsyncRequest, AsyncResponse, run_server_async};
+use rusty_web::status::Status;
+
+async fn home(_request: AsyncRequest) -> AsyncResponse {
+    return AsyncResponse::html(Status::Ok, "Home Page".to_string());
+}
+
+async fn user(request: AsyncRequest) -> AsyncResponse {
+    let id = request.path_param("id").unwrap_or_default();
+    return AsyncResponse::json(Status::Ok, format!("{{\"id\": \"{}\"}}", id));
+}
+
+#[tokio::main]
+async fn main() {
+    let paths: AsyncPaths = vec![
+        AsyncPath::new("/", home),
+        AsyncPath::with_method("GET", "/users/:id", user),
+    ];
+
+    if let Err(error) = run_server_async("0.0.0.0:8080", paths).await {
+        eprintln!("Failed to run server: {}", error);
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::{Display, Formatter};
+use crate::request::form::FormError;
+use crate::request::{Request, RequestError};
+use crate::response::Response;
+use crate::status::{Status, StatusCode};
+
+/// Error returned by the view. It is sent as an error response using the error renderer of the
+/// server config.
+#[derive(Debug)]
+pub struct HandlerError {
+    /// Status code of the error response
+    pub status: usize,
+    /// Logged by the server. It is not sent to the client.
+    pub message: String,
+}
+
+impl HandlerError {
+    pub fn new<T: StatusCode>(status: T, message: &str) -> Self {
+        return Self {
+            status: status.to_usize(),
+            message: message.to_string(),
+        };
+    }
+
+    /// Returns `400 Bad Request` error
+    pub fn bad_request(message: &str) -> Self {
+        return Self::new(Status::BadRequest, message);
+    }
+
+    /// Returns `404 Not Found` error
+    pub fn not_found(message: &str) -> Self {
+        return Self::new(Status::NotFound, message);
+    }
+
+    /// Returns `500 Internal Server Error` error
+    pub fn internal(message: &str) -> Self {
+        return Self::new(Status::InternalServerError, message);
+    }
+}
+
+impl Display for HandlerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{} {}", self.status, self.message);
+    }
+}
+
+impl std::error::Error for HandlerError {}
+
+impl From<std::io::Error> for HandlerError {
+    fn from(error: std::io::Error) -> Self {
+        return Self::internal(&error.to_string());
+    }
+}
+
+impl From<FormError> for HandlerError {
+    fn from(error: FormError) -> Self {
+        return Self::bad_request(&format!("{:?}", error));
+    }
+}
+
+impl From<RequestError> for HandlerError {
+    fn from(error: RequestError) -> Self {
+        return Self::bad_request(&error.to_string());
+    }
+}
+
+pub type HandlerResult = Result<Response, HandlerError>;
+
+/// View which returns the response instead of sending it. The returned response is sent by the
+/// server and the error is sent as an error response. Don't send the response from the view.
+///
+/// ```rust
+/// use rusty_web::handler::{HandlerError, HandlerResult};
+/// use rusty_web::paths::{Path, Paths};
+/// use rusty_web::request::Request;
+/// use rusty_web::response::Response;
+/// use rusty_web::status::Status;
+///
+/// fn profile(request: Request, mut response: Response) -> HandlerResult {
+///     let id = request.path_param("id").ok_or(HandlerError::not_found("Missing id"))?;
+///     response.html(Status::Ok, format!("User {}", id));
+///     return Ok(response);
+/// }
+///
+/// let paths: Paths = vec![
+///     Path::with_result("/users/:id", profile),
+/// ];
+/// ```
+pub type ResultView = fn(Request, Response) -> HandlerResult;
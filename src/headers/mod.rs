@@ -1,7 +1,9 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::io::Read;
 use std::net::TcpStream;
-use regex::Regex;
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::parser::parse_url_encoded;
 
 pub type Headers = HashMap<String, Vec<String>>;
@@ -15,16 +17,54 @@ pub enum RequestHeaderError {
     ClientDisconnected,
 }
 
+impl Display for RequestHeaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            RequestHeaderError::MaxSizeExceed => write!(f, "Request header is larger than the limit"),
+            RequestHeaderError::ClientDisconnected => write!(f, "Client disconnected before sending the header"),
+        };
+    }
+}
+
+impl Error for RequestHeaderError {}
+
 
 /// It will try to read headers from the tcp stream.
+/// Bytes already in `partial_body_bytes` are treated as the start of the request, for example
+/// a pipelined request read together with the previous one. After the headers are extracted, it
+/// contains the bytes read after the headers.
 /// Returns type `RequestHeaderError` if failed to extract headers.
 pub fn extract_headers(stream: &mut TcpStream, start_header: &mut String,
                        partial_body_bytes: &mut Vec<u8>, max_size: usize) -> Result<Headers, RequestHeaderError> {
-    let mut header_bytes = Vec::new();
+    return match read_header_block(stream, partial_body_bytes, max_size) {
+        Ok(header_block) => Ok(parse_header_block(&header_block, start_header)),
+        Err(error) => Err(error),
+    };
+}
+
 
-    let mut read_all_headers = false;
+/// Reads the raw header block including the request line, without the ending `\r\n\r\n`.
+/// Bytes read after the headers are left in `partial_body_bytes` same as `extract_headers`.
+pub fn read_header_block<R: Read>(stream: &mut R, partial_body_bytes: &mut Vec<u8>, max_size: usize)
+                         -> Result<String, RequestHeaderError> {
+    let mut header_bytes = std::mem::take(partial_body_bytes);
+    let mut search_from = 0;
+
+    loop {
+        // Header end is searched from the last few bytes of the previous read as "\r\n\r\n"
+        // may be split between two reads.
+        if let Some(position) = contains_full_headers(&header_bytes[search_from..]) {
+            let header_end_index = search_from + position;
+            if header_end_index > max_size {
+                return Err(RequestHeaderError::MaxSizeExceed);
+            }
+
+            // Body starts from header_end_index + "\r\n\r\n"
+            partial_body_bytes.extend(&header_bytes[header_end_index + 4..]);
+            header_bytes.truncate(header_end_index);
+            break;
+        }
 
-    while !read_all_headers {
         if header_bytes.len() > max_size {
             return Err(RequestHeaderError::MaxSizeExceed);
         }
@@ -47,22 +87,20 @@ pub fn extract_headers(stream: &mut TcpStream, start_header: &mut String,
             }
         }
 
-        // There will be index if the header is ended. However, contains_full_header don't take
-        // complete request header.
-        if let Some(header_end_index) = contains_full_headers(&buffer) {
-            header_bytes.extend(&buffer[..header_end_index]);
-
-            // Body starts from header_end_index + "\r\n\r\n"
-            partial_body_bytes.extend(&buffer[header_end_index + 4..read_size]);
-            read_all_headers = true;
-        } else {
-            header_bytes.extend(&buffer[..read_size]);
-        }
+        search_from = header_bytes.len().saturating_sub(3);
+        header_bytes.extend(&buffer[..read_size]);
     }
 
     let raw_request_headers = String::from_utf8(header_bytes)
         .expect("Unsupported header encoding.");
-    let header_lines: Vec<&str> = raw_request_headers.split("\r\n").collect();
+    return Ok(raw_request_headers);
+}
+
+
+/// Parses the header block returned by `read_header_block`. The request line is written to
+/// `start_header`.
+pub fn parse_header_block(header_block: &str, start_header: &mut String) -> Headers {
+    let header_lines: Vec<&str> = header_block.split("\r\n").collect();
 
     let mut headers: Headers = HashMap::new();
     for (index, header_line) in header_lines.iter().enumerate() {
@@ -83,10 +121,19 @@ pub fn extract_headers(stream: &mut TcpStream, start_header: &mut String,
         }
     };
 
-    return Ok(headers);
+    return headers;
 }
 
 
+/// Returns false if any `Content-Length` value is not a valid length. Body of such request can't
+/// be read.
+pub fn is_valid_content_length(headers: &Headers) -> bool {
+    return match headers.get("Content-Length") {
+        Some(values) => values.iter().all(|value| value.parse::<usize>().is_ok()),
+        None => true,
+    };
+}
+
 /// Returns content length from the `Header` if available
 pub fn content_length(headers: &Headers) -> Option<usize> {
     if let Some(values) = headers.get("Content-Length") {
@@ -113,6 +160,397 @@ pub fn connection_type(headers: &Headers) -> Option<String> {
     return None;
 }
 
+/// Returns true if the `Connection` header contains the given token. `Connection` is a comma
+/// separated list, for example: `keep-alive, Upgrade`. Tokens are compared case-insensitively.
+pub fn has_connection_token(headers: &Headers, token: &str) -> bool {
+    return header_list(headers, "Connection").iter().any(|value| value.eq_ignore_ascii_case(token));
+}
+
+/// Returns true if the client asked to keep the connection open. `close` takes precedence.
+pub fn is_keep_alive(headers: &Headers) -> bool {
+    return has_connection_token(headers, "keep-alive") && !has_connection_token(headers, "close");
+}
+
+/// Returns all the values of comma separated list header such as `Accept-Encoding`.
+/// Values from repeated headers are merged in the order they were received.
+pub fn header_list(headers: &Headers, name: &str) -> Vec<String> {
+    let mut list = Vec::new();
+
+    if let Some(values) = headers.get(name) {
+        for value in values {
+            list.extend(split_header_list(value));
+        }
+    }
+
+    return list;
+}
+
+/// Splits header value by comma. Commas inside quoted string are not treated as separator.
+/// Each item is trimmed and empty items are skipped.
+///
+/// Example:
+/// ```markdown
+/// gzip, deflate, br
+/// ```
+pub fn split_header_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for character in value.chars() {
+        if escaped {
+            current.push(character);
+            escaped = false;
+            continue;
+        }
+
+        if in_quotes && character == '\\' {
+            current.push(character);
+            escaped = true;
+            continue;
+        }
+
+        if character == '"' {
+            in_quotes = !in_quotes;
+        } else if character == ',' && !in_quotes {
+            let item = current.trim();
+            if !item.is_empty() {
+                items.push(item.to_string());
+            }
+
+            current.clear();
+            continue;
+        }
+
+        current.push(character);
+    }
+
+    let item = current.trim();
+    if !item.is_empty() {
+        items.push(item.to_string());
+    }
+
+    return items;
+}
+
+/// Parses `;` separated parameters of the header value such as `Content-Disposition`.
+/// Values can be either token or quoted string. Escaped characters of the quoted string are
+/// unescaped. Parameter names are returned in lowercase. Parameters without value are ignored.
+///
+/// Example:
+/// ```markdown
+/// name="a\"b"; filename*=UTF-8''a.txt
+/// ```
+pub fn parse_parameters(value: &str) -> Vec<(String, String)> {
+    let mut parameters = Vec::new();
+    let mut characters = value.chars().peekable();
+
+    loop {
+        // Skip separators before the parameter name
+        while let Some(character) = characters.peek() {
+            if *character == ';' || character.is_whitespace() {
+                characters.next();
+            } else {
+                break;
+            }
+        }
+
+        if characters.peek().is_none() {
+            break;
+        }
+
+        let mut name = String::new();
+        while let Some(character) = characters.next_if(|character| *character != '=' && *character != ';') {
+            name.push(character);
+        }
+
+        if characters.next_if_eq(&'=').is_none() {
+            // Parameter without value
+            continue;
+        }
+
+        while characters.next_if(|character| character.is_whitespace()).is_some() {}
+
+        let mut parameter_value = String::new();
+        if characters.next_if_eq(&'"').is_some() {
+            let mut escaped = false;
+
+            for character in characters.by_ref() {
+                if escaped {
+                    parameter_value.push(character);
+                    escaped = false;
+                } else if character == '\\' {
+                    escaped = true;
+                } else if character == '"' {
+                    break;
+                } else {
+                    parameter_value.push(character);
+                }
+            }
+        } else {
+            while let Some(character) = characters.next_if(|character| *character != ';') {
+                parameter_value.push(character);
+            }
+
+            parameter_value = parameter_value.trim_end().to_string();
+        }
+
+        parameters.push((name.trim().to_lowercase(), parameter_value));
+    }
+
+    return parameters;
+}
+
+/// Returns `Content-Disposition` header value for the given disposition type and filename.
+/// Quotes and backslashes are escaped. If the filename contains non ASCII characters, RFC 5987
+/// `filename*` parameter is also added with UTF-8 percent encoded filename.
+///
+/// Example:
+/// ```markdown
+/// attachment; filename="report.pdf"
+/// attachment; filename="r_sum_.pdf"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf
+/// ```
+pub fn content_disposition(disposition_type: &str, filename: &str) -> String {
+    let mut fallback = String::new();
+
+    for character in filename.chars() {
+        if !character.is_ascii() || character.is_ascii_control() {
+            fallback.push('_');
+        } else if character == '"' || character == '\\' {
+            fallback.push('\\');
+            fallback.push(character);
+        } else {
+            fallback.push(character);
+        }
+    }
+
+    let mut value = format!("{}; filename=\"{}\"", disposition_type, fallback);
+    if !filename.is_ascii() {
+        let encoded = urlencoding::encode(filename);
+        value.push_str(&format!("; filename*=UTF-8''{}", encoded));
+    }
+
+    return value;
+}
+
+/// Decodes RFC 5987 extended parameter value such as `filename*`. UTF-8 and ISO-8859-1 charsets
+/// are supported. Returns `None` if the value is invalid.
+///
+/// Example:
+/// ```markdown
+/// UTF-8''r%C3%A9sum%C3%A9.pdf
+/// ```
+pub fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    let bytes = urlencoding::decode_binary(encoded.as_bytes());
+    if charset.eq_ignore_ascii_case("UTF-8") {
+        return String::from_utf8(bytes.into_owned()).ok();
+    } else if charset.eq_ignore_ascii_case("ISO-8859-1") {
+        return Some(bytes.iter().map(|byte| *byte as char).collect());
+    }
+
+    return None;
+}
+
+/// Preset of the common security headers. Each header is enabled by default and can be disabled
+/// by setting it to `None` or `false`. Registered in the server config as default headers.
+///
+/// ```rust
+/// use rusty_web::headers::SecurityHeaders;
+/// use rusty_web::server::Config;
+///
+/// let security_headers = SecurityHeaders {
+///     content_security_policy: None,
+///     ..SecurityHeaders::default()
+/// };
+///
+/// let config = Config {
+///     default_headers: security_headers.to_headers(),
+///     ..Config::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    /// `max-age` of `Strict-Transport-Security` in seconds
+    pub hsts_max_age: Option<u64>,
+    /// Sends `X-Content-Type-Options: nosniff`
+    pub content_type_options: bool,
+    /// Value of `X-Frame-Options`
+    pub frame_options: Option<String>,
+    /// Value of `Content-Security-Policy`
+    pub content_security_policy: Option<String>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        return Self {
+            hsts_max_age: Some(365 * 24 * 60 * 60), // 1 year
+            content_type_options: true,
+            frame_options: Some("DENY".to_string()),
+            content_security_policy: Some("default-src 'self'".to_string()),
+        };
+    }
+}
+
+impl SecurityHeaders {
+    /// Returns the enabled headers as name and value pairs
+    pub fn to_headers(&self) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+
+        if let Some(max_age) = self.hsts_max_age {
+            headers.push(("Strict-Transport-Security".to_string(), format!("max-age={}", max_age)));
+        }
+
+        if self.content_type_options {
+            headers.push(("X-Content-Type-Options".to_string(), "nosniff".to_string()));
+        }
+
+        if let Some(frame_options) = &self.frame_options {
+            headers.push(("X-Frame-Options".to_string(), frame_options.to_string()));
+        }
+
+        if let Some(policy) = &self.content_security_policy {
+            headers.push(("Content-Security-Policy".to_string(), policy.to_string()));
+        }
+
+        return headers;
+    }
+}
+
+/// Single byte range requested using `Range` header.
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    /// Inclusive start and end position of the range
+    Satisfiable(u64, u64),
+    /// Range starts after the end of the resource
+    Unsatisfiable,
+}
+
+/// Parses `Range` header value for the resource of given length. Returns `None` if the value is
+/// invalid or contains multiple ranges, in which case the header should be ignored.
+///
+/// Example:
+/// ```markdown
+/// bytes=0-499
+/// bytes=500-
+/// bytes=-500
+/// ```
+pub fn parse_byte_range(value: &str, length: u64) -> Option<ByteRange> {
+    let range = value.trim().strip_prefix("bytes=")?.trim();
+    if range.contains(',') {
+        return None;
+    }
+
+    let (start, end) = range.split_once('-')?;
+    let start = start.trim();
+    let end = end.trim();
+
+    if start.is_empty() {
+        // Suffix range: last n bytes
+        let suffix_length = end.parse::<u64>().ok()?;
+        if suffix_length == 0 || length == 0 {
+            return Some(ByteRange::Unsatisfiable);
+        }
+
+        let suffix_length = suffix_length.min(length);
+        return Some(ByteRange::Satisfiable(length - suffix_length, length - 1));
+    }
+
+    let start = start.parse::<u64>().ok()?;
+    let end = if end.is_empty() {
+        u64::MAX
+    } else {
+        end.parse::<u64>().ok()?
+    };
+
+    if end < start {
+        return None;
+    }
+
+    if start >= length {
+        return Some(ByteRange::Unsatisfiable);
+    }
+
+    return Some(ByteRange::Satisfiable(start, end.min(length - 1)));
+}
+
+/// Compares the entity tags using strong comparison. Tags match only if both are strong and
+/// identical. Used by `If-Range`.
+pub fn etag_strong_match(first: &str, second: &str) -> bool {
+    let first = first.trim();
+    let second = second.trim();
+    return !first.starts_with("W/") && !second.starts_with("W/") && first == second;
+}
+
+/// Compares the entity tags using weak comparison. Tags match if they are identical after
+/// removing the `W/` prefix. Used by `If-None-Match`.
+pub fn etag_weak_match(first: &str, second: &str) -> bool {
+    return opaque_tag(first) == opaque_tag(second);
+}
+
+/// Returns the entity tag without the weak `W/` prefix
+fn opaque_tag(etag: &str) -> &str {
+    let etag = etag.trim();
+    return etag.strip_prefix("W/").unwrap_or(etag);
+}
+
+/// Returns true if any tag of the `If-None-Match` header matches the current entity tag using
+/// weak comparison. `*` matches any current representation. Returns false if there is no header.
+pub fn if_none_match(headers: &Headers, etag: &str) -> bool {
+    return header_list(headers, "If-None-Match").iter()
+        .any(|tag| tag == "*" || etag_weak_match(tag, etag));
+}
+
+/// Returns true if there is no `If-Match` header or any of its tags matches the current entity
+/// tag using strong comparison. `*` matches any current representation. Unsafe methods such as
+/// `PUT` and `DELETE` must respond with `412 Precondition Failed` if it returns false.
+pub fn if_match(headers: &Headers, etag: &str) -> bool {
+    if !headers.contains_key("If-Match") {
+        return true;
+    }
+
+    return header_list(headers, "If-Match").iter()
+        .any(|tag| tag == "*" || etag_strong_match(tag, etag));
+}
+
+/// Formats the time as HTTP date used by headers such as `Last-Modified`.
+///
+/// Example:
+/// ```markdown
+/// Sun, 06 Nov 1994 08:49:37 GMT
+/// ```
+pub fn http_date(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => 0,
+    };
+
+    let days = seconds / 86400;
+    let seconds_of_day = seconds % 86400;
+    let week_days = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    let week_day = week_days[(days % 7) as usize];
+
+    // Converts days since epoch to civil date
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let months = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    return format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT", week_day, day, months[(month - 1) as usize],
+                   year, seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60);
+}
+
 /// Returns `Host` value from the Header if available.
 pub fn host(headers: &Headers) -> Option<String> {
     let host = headers.get("Host");
@@ -149,12 +587,40 @@ pub fn contains_full_headers(buffer: &[u8]) -> Option<usize> {
 /// GET / HTTP/1.1
 /// ```
 pub fn parse_request_method_header(line: &str) -> Option<(String, String)> {
-    let pattern = Regex::new(r"(?<method>.+) (?<path>.+) (.+)").unwrap();
+    // Same as matching `(.+) (.+) (.+)` where `.` doesn't match `\n`
+    for segment in line.split('\n') {
+        if let Some(parts) = split_request_line(segment) {
+            return Some(parts);
+        }
+    }
+
+    return None;
+}
+
+/// Splits the line into method and path. Both are matched greedily, so the last space before
+/// the version and the last space before the path are used as separators.
+fn split_request_line(line: &str) -> Option<(String, String)> {
+    let bytes = line.as_bytes();
+
+    // Version needs at least one byte after the space
+    let version_space = (0..bytes.len().saturating_sub(1)).rev().find(|&index| bytes[index] == b' ')?;
+    // Method and path need at least one byte each
+    let path_space = (1..version_space.saturating_sub(1)).rev().find(|&index| bytes[index] == b' ')?;
+
+    let method = &line[..path_space];
+    let path = &line[path_space + 1..version_space];
+    return Some((method.to_string(), path.to_string()));
+}
 
-    if let Some(groups) = pattern.captures(line) {
-        let request_method = &groups["method"];
-        let path = &groups["path"];
-        return Some((request_method.to_string(), path.to_string()));
+/// Returns the HTTP version from the request line if available
+/// ```markdown
+/// GET / HTTP/1.0
+/// ```
+pub fn parse_request_version(line: &str) -> Option<String> {
+    let version = line.trim_end().rsplit(' ').next()?;
+
+    if version.starts_with("HTTP/") {
+        return Some(version.to_string());
     }
 
     return None;
@@ -195,3 +661,227 @@ pub fn query_params_from_raw(raw_path: &String) -> HashMap<String, Vec<String>>
     let slice = &raw_path[index + 1..raw_path.len()];
     return parse_url_encoded(slice);
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::error::Error;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::headers::{ByteRange, SecurityHeaders, content_disposition, etag_strong_match, etag_weak_match, has_connection_token, if_match, if_none_match, is_keep_alive, decode_ext_value, header_list, parse_header_block, parse_request_method_header, parse_parameters, parse_request_version, Headers, http_date,
+                         parse_byte_range,
+                         split_header_list, RequestHeaderError};
+
+    #[test]
+    fn test_parse_request_method_header() {
+        assert_eq!(Some(("GET".to_string(), "/".to_string())), parse_request_method_header("GET / HTTP/1.1"));
+        assert_eq!(None, parse_request_method_header("GET /"));
+        assert_eq!(None, parse_request_method_header(""));
+
+        // Hand written parser must match the previously used regex exactly
+        let pattern = regex::Regex::new(r"(?<method>.+) (?<path>.+) (.+)").unwrap();
+        let lines = [
+            "GET / HTTP/1.1", "GET /a b HTTP/1.1", "GET  HTTP/1.1", "GET /  HTTP/1.1", "GET / HTTP/1.1 ",
+            " GET / HTTP/1.1", "a b c", "a  b c", "a b  c", "ab c", "GET / HTTP/1.1\r", "x\nGET / HTTP/1.1",
+            "GET / \nHTTP/1.1", "GET /\n / HTTP/1.1", "   ", "    ", "     ", "é ü ñ", "GET /ü HTTP/1.1",
+        ];
+
+        // Every line up to 6 characters of the alphabet, so that a failure is reproducible
+        let mut generated_lines = vec![String::new()];
+        let mut previous_lines = vec![String::new()];
+        for _ in 0..6 {
+            let next_lines: Vec<String> = previous_lines.iter()
+                .flat_map(|line| ['a', ' ', '\n', '\r', 'é'].map(|character| format!("{}{}", line, character)))
+                .collect();
+            generated_lines.extend(next_lines.clone());
+            previous_lines = next_lines;
+        }
+
+        for line in lines.iter().map(|line| line.to_string()).chain(generated_lines) {
+            let expected = pattern.captures(&line)
+                .map(|groups| (groups["method"].to_string(), groups["path"].to_string()));
+            assert_eq!(expected, parse_request_method_header(&line), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_parse_header_block() {
+        let mut start_header = String::new();
+        let headers = parse_header_block("GET / HTTP/1.1\r\nHost: localhost\r\nX-Tag: a\r\nX-Tag:b", &mut start_header);
+
+        assert_eq!("GET / HTTP/1.1", start_header);
+        assert_eq!(vec!["localhost"], headers["Host"]);
+        assert_eq!(vec!["a", "b"], headers["X-Tag"]);
+    }
+
+    #[test]
+    fn test_etag_comparison() {
+        assert_eq!(true, etag_strong_match("\"1\"", "\"1\""));
+        assert_eq!(false, etag_strong_match("W/\"1\"", "\"1\""));
+        assert_eq!(false, etag_strong_match("W/\"1\"", "W/\"1\""));
+        assert_eq!(false, etag_strong_match("\"1\"", "\"2\""));
+
+        assert_eq!(true, etag_weak_match("\"1\"", "\"1\""));
+        assert_eq!(true, etag_weak_match("W/\"1\"", "\"1\""));
+        assert_eq!(true, etag_weak_match("W/\"1\"", "W/\"1\""));
+        assert_eq!(false, etag_weak_match("W/\"1\"", "W/\"2\""));
+    }
+
+    #[test]
+    fn test_if_match() {
+        let mut headers: Headers = HashMap::new();
+        assert_eq!(true, if_match(&headers, "\"abc\""));
+
+        headers.insert("If-Match".to_string(), vec!["\"x\", \"abc\"".to_string()]);
+        assert_eq!(true, if_match(&headers, "\"abc\""));
+        assert_eq!(false, if_match(&headers, "\"ab\""));
+        assert_eq!(false, if_match(&headers, "W/\"abc\""));
+
+        headers.insert("If-Match".to_string(), vec!["W/\"abc\"".to_string()]);
+        assert_eq!(false, if_match(&headers, "\"abc\""));
+
+        headers.insert("If-Match".to_string(), vec!["*".to_string()]);
+        assert_eq!(true, if_match(&headers, "\"anything\""));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let mut headers: Headers = HashMap::new();
+        assert_eq!(false, if_none_match(&headers, "\"abc\""));
+
+        headers.insert("If-None-Match".to_string(), vec!["\"x\", W/\"abc\"".to_string()]);
+        assert_eq!(true, if_none_match(&headers, "\"abc\""));
+        assert_eq!(true, if_none_match(&headers, "W/\"x\""));
+        assert_eq!(false, if_none_match(&headers, "\"ab\""));
+
+        headers.insert("If-None-Match".to_string(), vec!["\"a,b\"".to_string()]);
+        assert_eq!(true, if_none_match(&headers, "\"a,b\""));
+        assert_eq!(false, if_none_match(&headers, "\"a\""));
+
+        headers.insert("If-None-Match".to_string(), vec!["*".to_string()]);
+        assert_eq!(true, if_none_match(&headers, "W/\"anything\""));
+    }
+
+    #[test]
+    fn test_split_header_list() {
+        assert_eq!(vec!["gzip", "deflate", "br"], split_header_list("gzip, deflate, br"));
+        assert_eq!(vec!["gzip", "br"], split_header_list("  gzip  ,, br ,  "));
+        assert_eq!(0, split_header_list("").len());
+    }
+
+    #[test]
+    fn test_split_header_list_quoted() {
+        let values = split_header_list(r#"text/html; q="a, b", foo="x\", y", bar"#);
+        assert_eq!(vec![r#"text/html; q="a, b""#, r#"foo="x\", y""#, "bar"], values);
+    }
+
+    #[test]
+    fn test_header_list_repeated() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Allow".to_string(), vec!["GET, HEAD".to_string(), "POST".to_string()]);
+
+        assert_eq!(vec!["GET", "HEAD", "POST"], header_list(&headers, "Allow"));
+        assert_eq!(0, header_list(&headers, "Accept-Encoding").len());
+    }
+
+    #[test]
+    fn test_content_disposition_ascii() {
+        assert_eq!(r#"attachment; filename="report.pdf""#, content_disposition("attachment", "report.pdf"));
+        assert_eq!(r#"attachment; filename="a\"b.txt""#, content_disposition("attachment", "a\"b.txt"));
+    }
+
+    #[test]
+    fn test_content_disposition_utf8() {
+        let value = content_disposition("attachment", "résumé.pdf");
+        assert_eq!(r#"attachment; filename="r_sum_.pdf"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"#, value);
+    }
+
+    #[test]
+    fn test_parse_parameters() {
+        let parameters = parse_parameters(r#"name="a\"b;c"; creation-date="today"; Size=10 ; flag; filename*=UTF-8''a.txt"#);
+        assert_eq!(vec![
+            ("name".to_string(), "a\"b;c".to_string()),
+            ("creation-date".to_string(), "today".to_string()),
+            ("size".to_string(), "10".to_string()),
+            ("filename*".to_string(), "UTF-8''a.txt".to_string()),
+        ], parameters);
+
+        assert_eq!(0, parse_parameters("").len());
+    }
+
+    #[test]
+    fn test_decode_ext_value() {
+        assert_eq!(Some("résumé.pdf".to_string()), decode_ext_value("UTF-8''r%C3%A9sum%C3%A9.pdf"));
+        assert_eq!(Some("résumé.pdf".to_string()), decode_ext_value("iso-8859-1'en'r%E9sum%E9.pdf"));
+        assert_eq!(None, decode_ext_value("r%C3%A9sum%C3%A9.pdf"));
+        assert_eq!(None, decode_ext_value("UTF-8''%FF.pdf"));
+        assert_eq!(None, decode_ext_value("UTF-16''a.pdf"));
+    }
+
+    #[test]
+    fn test_parse_byte_range() {
+        assert_eq!(Some(ByteRange::Satisfiable(0, 4)), parse_byte_range("bytes=0-4", 10));
+        assert_eq!(Some(ByteRange::Satisfiable(5, 9)), parse_byte_range("bytes=5-", 10));
+        assert_eq!(Some(ByteRange::Satisfiable(7, 9)), parse_byte_range("bytes=-3", 10));
+        assert_eq!(Some(ByteRange::Satisfiable(2, 9)), parse_byte_range("bytes=2-100", 10));
+        assert_eq!(Some(ByteRange::Unsatisfiable), parse_byte_range("bytes=10-", 10));
+        assert_eq!(None, parse_byte_range("bytes=0-1,3-4", 10));
+        assert_eq!(None, parse_byte_range("items=0-4", 10));
+    }
+
+    #[test]
+    fn test_http_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", http_date(time));
+        assert_eq!("Thu, 01 Jan 1970 00:00:00 GMT", http_date(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_header_error_display() {
+        let error: Box<dyn Error> = Box::new(RequestHeaderError::MaxSizeExceed);
+        assert_eq!("Request header is larger than the limit", error.to_string());
+        assert_eq!(true, error.source().is_none());
+    }
+
+    #[test]
+    fn test_parse_request_version() {
+        assert_eq!(Some("HTTP/1.0".to_string()), parse_request_version("GET / HTTP/1.0"));
+        assert_eq!(Some("HTTP/1.1".to_string()), parse_request_version("GET /a%20b HTTP/1.1\r\n"));
+        assert_eq!(None, parse_request_version("GET /"));
+    }
+
+    #[test]
+    fn test_security_headers() {
+        let headers = SecurityHeaders::default().to_headers();
+        assert_eq!(4, headers.len());
+        assert_eq!(true, headers.contains(&("Strict-Transport-Security".to_string(), "max-age=31536000".to_string())));
+        assert_eq!(true, headers.contains(&("X-Content-Type-Options".to_string(), "nosniff".to_string())));
+        assert_eq!(true, headers.contains(&("X-Frame-Options".to_string(), "DENY".to_string())));
+        assert_eq!(true, headers.contains(&("Content-Security-Policy".to_string(), "default-src 'self'".to_string())));
+
+        let security_headers = SecurityHeaders {
+            hsts_max_age: None,
+            content_type_options: false,
+            ..SecurityHeaders::default()
+        };
+
+        let headers = security_headers.to_headers();
+        assert_eq!(2, headers.len());
+        assert_eq!(false, headers.iter().any(|(name, _)| name == "Strict-Transport-Security"));
+        assert_eq!(false, headers.iter().any(|(name, _)| name == "X-Content-Type-Options"));
+    }
+
+    #[test]
+    fn test_connection_tokens() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["Keep-Alive, Upgrade".to_string()]);
+        assert_eq!(true, has_connection_token(&headers, "upgrade"));
+        assert_eq!(true, is_keep_alive(&headers));
+
+        headers.insert("Connection".to_string(), vec!["keep-alive".to_string(), "close".to_string()]);
+        assert_eq!(false, is_keep_alive(&headers));
+
+        headers.insert("Connection".to_string(), vec!["upgrade".to_string()]);
+        assert_eq!(false, is_keep_alive(&headers));
+        assert_eq!(false, is_keep_alive(&HashMap::new()));
+    }
+}
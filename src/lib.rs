@@ -3,42 +3,137 @@ pub mod parser;
 pub mod request;
 pub mod headers;
 pub mod response;
+pub mod router;
+pub mod handler;
+pub mod middleware;
+pub mod testing;
+#[cfg(feature = "async")]
+pub mod server_async;
 
 pub mod paths {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::handler::ResultView;
     use crate::request::Request;
     use crate::response::Response;
 
-    pub type Paths = Vec<Path<fn(Request, Response)>>;
-    pub type SinglePath = Path<fn(Request, Response)>;
+    pub type Paths = Vec<Path<View>>;
+    pub type SinglePath = Path<View>;
+
+    /// View of the path. The server calls the view based on its kind.
+    #[derive(Clone)]
+    pub enum View {
+        /// View which sends the response itself
+        Plain(Arc<dyn Fn(Request, Response) + Send + Sync>),
+        /// View which returns the response. See `Path::with_result`
+        Result(ResultView),
+    }
+
+    impl<F: Fn(Request, Response) + Send + Sync + 'static> From<F> for View {
+        fn from(view: F) -> Self {
+            return View::Plain(Arc::new(view));
+        }
+    }
+
+    /// Decides whether the request should be passed to the view after running the middleware
+    pub enum Flow {
+        Continue,
+        /// Stops processing the request. The middleware must send the response itself.
+        Halt,
+    }
+
+    /// Middleware runs before the view in the order they are added
+    pub type Middleware = Arc<dyn Fn(&mut Request) -> Flow + Send + Sync>;
+
+    /// Limits applied to the request before passing it to the view. Empty values use the
+    /// server defaults.
+    #[derive(Debug, Clone, Default)]
+    pub struct RouteConfig {
+        /// Maximum body size accepted by `body`, `form_data` and `files`
+        pub max_body_size: Option<usize>,
+        /// Read timeout of the client stream while reading the request body
+        pub read_timeout: Option<Duration>,
+    }
 
     /// Path accepts pathname and view
     pub struct Path<T> {
         pub name: String,
         pub view: T,
+        /// Request method served by this path. If it is `None`, all the methods are served.
+        pub method: Option<String>,
+        pub middlewares: Vec<Middleware>,
+        /// Name used to generate url of this path. Example: `user_detail`
+        pub route_name: Option<String>,
+        pub config: RouteConfig,
     }
 
     impl<T> Path<T> {
-        pub fn new(name: &str, view: T) -> Self {
+        pub fn new<V: Into<T>>(name: &str, view: V) -> Self {
             let name = name.to_string();
 
             return Self {
                 name,
-                view,
+                view: view.into(),
+                method: None,
+                middlewares: Vec::new(),
+                route_name: None,
+                config: RouteConfig::default(),
             };
         }
+
+        /// Path which only serves the given request method
+        pub fn with_method<V: Into<T>>(method: &str, name: &str, view: V) -> Self {
+            let mut path = Self::new(name, view);
+            path.method = Some(method.to_uppercase());
+            return path;
+        }
+
+        /// Sets the limits applied to the request of this path
+        pub fn with_config(mut self, config: RouteConfig) -> Self {
+            self.config = config;
+            return self;
+        }
+
+        /// Returns true if the path serves the given request method
+        pub fn allows_method(&self, method: &str) -> bool {
+            if let Some(path_method) = &self.method {
+                return path_method.eq_ignore_ascii_case(method);
+            }
+
+            return true;
+        }
+    }
+
+    impl Path<View> {
+        /// Path with a view which returns the response. The server sends the returned response
+        /// or the error response.
+        pub fn with_result(name: &str, view: ResultView) -> Self {
+            return Self::new(name, View::Result(view));
+        }
     }
 }
 
 
 pub mod server {
-    use std::net::{Shutdown, TcpListener, TcpStream};
-    use std::sync::{Arc, RwLock};
-    use std::sync::atomic::{AtomicBool, Ordering};
-    use std::thread::spawn;
-    use crate::headers::{parse_request_method_header, extract_headers};
-    use crate::paths::{Paths, SinglePath};
+    use std::collections::{BTreeSet, HashMap};
+    use std::io;
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, TcpListener, TcpStream};
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+    use std::thread::{sleep, spawn, Builder};
+    use std::time::{Duration, Instant};
+    use crate::handler::ResultView;
+    use crate::headers::{is_valid_content_length, parse_request_method_header, parse_request_version, parse_header_block,
+                         read_header_block, Headers, RequestHeaderError};
+    use crate::parser::content_encoding::DecodeLimits;
+    use crate::paths::{Flow, Paths, SinglePath, View};
     use crate::request::{Request};
     use crate::response::Response;
+    use crate::router::{resolve_path, MatchResult};
+    use crate::status::{Status, StatusMethods};
 
     /// Example usage
     /// ```rust
@@ -61,12 +156,26 @@ pub mod server {
     /// }
     /// ```
     pub fn run_server(listen_address: &str, paths: Paths) {
-        println!("Running server in: http://{}", listen_address);
-        let tcp = TcpListener::bind(listen_address);
+        run_server_with_config(listen_address, paths, Config::default());
+    }
+
+    /// Same as `run_server` but uses the given server configuration
+    pub fn run_server_with_config(listen_address: &str, paths: Paths, config: Config) {
+        let tcp = bind_address(listen_address);
 
         match tcp {
             Ok(listener) => {
-                listen_connections(listener, paths);
+                match listener.local_addr() {
+                    Ok(address) => {
+                        println!("Running server in: http://{}", address);
+                    }
+
+                    Err(_) => {
+                        println!("Running server in: http://{}", listen_address);
+                    }
+                }
+
+                listen_connections_with_config(listener, paths, config);
             }
 
             Err(_) => {
@@ -75,23 +184,463 @@ pub mod server {
         }
     }
 
+    /// Binds the listen address such as `127.0.0.1:8080` or IPv6 `[::1]:8080`. Port only address
+    /// like `:8080` binds to all the interfaces of both families using `bind_dual_stack`.
+    pub fn bind_address(listen_address: &str) -> io::Result<TcpListener> {
+        if let Some(port) = listen_address.strip_prefix(':') {
+            return match port.parse::<u16>() {
+                Ok(port) => bind_dual_stack(port),
+                Err(_) => Err(io::Error::new(ErrorKind::InvalidInput, "Invalid port")),
+            };
+        }
+
+        return TcpListener::bind(listen_address);
+    }
+
+    /// Binds `[::]:port` which accepts both IPv6 and IPv4 connections on the systems where IPv6
+    /// sockets are dual stack by default (Linux, macOS). Falls back to `0.0.0.0:port` if IPv6 is
+    /// not available.
+    pub fn bind_dual_stack(port: u16) -> io::Result<TcpListener> {
+        return match TcpListener::bind((Ipv6Addr::UNSPECIFIED, port)) {
+            Ok(listener) => Ok(listener),
+            Err(_) => TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)),
+        };
+    }
+
+    /// Serves the paths using a listener which is already bound by the caller. It is useful for
+    /// tests which bind to port `0` and read the assigned port before serving.
+    ///
+    /// ```no_run
+    /// use std::net::TcpListener;
+    /// use rusty_web::paths::Paths;
+    /// use rusty_web::server::run_server_with_listener;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// println!("Assigned port: {}", listener.local_addr().unwrap().port());
+    ///
+    /// let paths: Paths = vec![];
+    /// run_server_with_listener(listener, paths);
+    /// ```
+    pub fn run_server_with_listener(listener: TcpListener, paths: Paths) {
+        match listener.local_addr() {
+            Ok(address) => {
+                println!("Running server in: http://{}", address);
+            }
+
+            Err(_) => {
+                println!("Running server with provided listener");
+            }
+        }
+
+        listen_connections(listener, paths);
+    }
+
+    /// Serves the paths using an already listening socket file descriptor. This is mainly used
+    /// for socket activation where the listening socket is passed by systemd.
+    ///
+    /// # Safety
+    /// The file descriptor must be a valid listening TCP socket owned by the caller. The ownership
+    /// is transferred to the server.
+    #[cfg(unix)]
+    pub unsafe fn run_server_with_fd(fd: std::os::unix::io::RawFd, paths: Paths) {
+        use std::os::unix::io::FromRawFd;
+
+        let listener = TcpListener::from_raw_fd(fd);
+        run_server_with_listener(listener, paths);
+    }
+
     pub fn listen_connections(listener: TcpListener, paths: Paths) {
+        listen_connections_with_config(listener, paths, Config::default());
+    }
+
+    /// Accepted connections which are not closed yet. Used for draining the connections on shutdown.
+    type Connections = Arc<Mutex<HashMap<usize, (Arc<TcpStream>, Arc<Context>)>>>;
+
+    pub fn listen_connections_with_config(listener: TcpListener, paths: Paths, config: Config) {
         let paths_lock = Arc::new(RwLock::new(paths));
+        let config = Arc::new(config);
+        let connections: Connections = Arc::new(Mutex::new(HashMap::new()));
+        let shutdown = config.shutdown.clone();
+
+        // Accept is polled so that the shutdown request is noticed without a new connection
+        if shutdown.is_some() && listener.set_nonblocking(true).is_err() {
+            eprintln!("Failed to set listener non blocking. Shutdown is noticed on next connection.");
+        }
+
+        let mut connection_id = 0;
+        let mut accept_backoff = Duration::ZERO;
+
+        loop {
+            if let Some(shutdown) = &shutdown {
+                if shutdown.is_shutdown() {
+                    break;
+                }
+            }
+
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    accept_backoff = Duration::ZERO;
+
+                    // Accepted stream may inherit non blocking mode of the listener
+                    let _ = stream.set_nonblocking(false);
+
+                    if let Some(max_connections) = config.max_connections {
+                        if connections.lock().unwrap().len() >= max_connections {
+                            reject_connection(stream);
+                            continue;
+                        }
+                    }
 
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
                     let paths = Arc::clone(&paths_lock);
+                    let context = Arc::new(Context::new(Arc::clone(&config)));
+
+                    // Stream is shared with the connection list so that it can be closed on shutdown
+                    let stream = Arc::new(stream);
+                    connection_id += 1;
+                    connections.lock().unwrap().insert(connection_id, (stream.clone(), context.clone()));
+
+                    if let Some(metrics) = &config.metrics {
+                        metrics.connection_opened();
+                    }
 
-                    spawn(move || {
-                        serve_client(stream, paths);
+                    let guard = ConnectionGuard {
+                        connections: Arc::clone(&connections),
+                        id: connection_id,
+                        metrics: config.metrics.clone(),
+                    };
+
+                    let thread_name = format!("{}-{}", config.thread_name_prefix, connection_id);
+                    let spawn_result = Builder::new().name(thread_name).spawn(move || {
+                        let _guard = guard;
+                        serve_client(stream, paths, context);
                     });
+
+                    if let Err(error) = spawn_result {
+                        eprintln!("Error spawning connection thread: {}", error);
+                    }
+                }
+
+                Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                    sleep(Duration::from_millis(50));
                 }
 
                 Err(error) => {
-                    print!("Error receiving stream: {}", error);
+                    accept_backoff = next_accept_backoff(&error, accept_backoff);
+                    if !accept_backoff.is_zero() {
+                        eprintln!("Error accepting connection: {}. Retrying in {:?}", error, accept_backoff);
+                        sleep(accept_backoff);
+                    }
+                }
+            }
+        }
+
+        println!("Shutting down server");
+        drain_connections(&connections, config.shutdown_timeout);
+    }
+
+    /// Sends `503 Service Unavailable` without parsing the request and closes the connection
+    fn reject_connection(mut stream: TcpStream) {
+        spawn(move || {
+            let _ = stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            let _ = stream.shutdown(Shutdown::Write);
+
+            // Closing with the unread request resets the connection before the client reads the response
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+            let _ = std::io::copy(&mut stream.take(64 * 1024), &mut std::io::sink());
+        });
+    }
+
+    /// Maximum delay between the accept retries after the listener error
+    const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+    /// Returns the delay before accepting again. Errors of the single connection are retried
+    /// immediately. Other errors, such as too many open files, would fail again at once, so the
+    /// delay is doubled on every error.
+    pub(crate) fn next_accept_backoff(error: &std::io::Error, previous: Duration) -> Duration {
+        match error.kind() {
+            ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::Interrupted => {
+                return Duration::ZERO;
+            }
+
+            _ => {
+                let backoff = if previous.is_zero() { Duration::from_millis(10) } else { previous * 2 };
+                return backoff.min(MAX_ACCEPT_BACKOFF);
+            }
+        }
+    }
+
+    /// Removes the connection when the connection thread exits, even if the view panics
+    struct ConnectionGuard {
+        connections: Connections,
+        id: usize,
+        metrics: Option<MetricsHandle>,
+    }
+
+    impl Drop for ConnectionGuard {
+        fn drop(&mut self) {
+            if let Ok(mut connections) = self.connections.lock() {
+                connections.remove(&self.id);
+            }
+
+            if let Some(metrics) = &self.metrics {
+                metrics.connection_closed();
+            }
+        }
+    }
+
+    /// Closes idle keep-alive connections and waits for the active requests to complete.
+    /// Connections which are still active after the timeout are closed forcefully.
+    fn drain_connections(connections: &Connections, timeout: Duration) {
+        let started = Instant::now();
+
+        while started.elapsed() < timeout {
+            {
+                let connections = connections.lock().unwrap();
+                if connections.is_empty() {
+                    return;
+                }
+
+                for (stream, context) in connections.values() {
+                    context.dont_wait();
+
+                    if !context.serving.load(Ordering::Relaxed) {
+                        let _ = stream.shutdown(Shutdown::Both);
+                    }
                 }
             }
+
+            sleep(Duration::from_millis(50));
+        }
+
+        eprintln!("Closing active connections after shutdown timeout");
+        for (stream, _) in connections.lock().unwrap().values() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Requests graceful shutdown of the server. It can be cloned and used from any thread.
+    ///
+    /// ```no_run
+    /// use rusty_web::paths::Paths;
+    /// use rusty_web::server::{Config, run_server_with_config, ShutdownHandle};
+    ///
+    /// let shutdown = ShutdownHandle::new();
+    /// let config = Config {
+    ///     shutdown: Some(shutdown.clone()),
+    ///     ..Config::default()
+    /// };
+    ///
+    /// // Call `shutdown.shutdown()` from another thread to stop the server
+    /// let paths: Paths = vec![];
+    /// run_server_with_config("0.0.0.0:8080", paths, config);
+    /// ```
+    #[derive(Clone, Default)]
+    pub struct ShutdownHandle {
+        requested: Arc<AtomicBool>,
+    }
+
+    impl ShutdownHandle {
+        pub fn new() -> Self {
+            return Self::default();
+        }
+
+        /// Stops accepting new connections. Server returns after the active requests are completed
+        /// or `Config::shutdown_timeout` is elapsed.
+        pub fn shutdown(&self) {
+            self.requested.store(true, Ordering::SeqCst);
+        }
+
+        pub fn is_shutdown(&self) -> bool {
+            return self.requested.load(Ordering::SeqCst);
+        }
+
+        /// Requests shutdown when the process receives SIGTERM or SIGINT. If the signal is received
+        /// again while shutting down, the process is terminated immediately.
+        #[cfg(feature = "signals")]
+        pub fn shutdown_on_signals(&self) -> std::io::Result<()> {
+            use signal_hook::consts::{SIGINT, SIGTERM};
+
+            for signal in [SIGTERM, SIGINT] {
+                // Order matters: the conditional shutdown only triggers if the flag is already set
+                signal_hook::flag::register_conditional_shutdown(signal, 1, self.requested.clone())?;
+                signal_hook::flag::register(signal, self.requested.clone())?;
+            }
+
+            return Ok(());
+        }
+    }
+
+    /// Snapshot of the server counters. See `MetricsHandle::metrics`.
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct Metrics {
+        pub connections_accepted: usize,
+        pub active_connections: usize,
+        pub requests: usize,
+        /// Responses by status class. Index 0 is `1xx` and index 4 is `5xx`.
+        pub responses: [usize; 5],
+    }
+
+    #[derive(Default)]
+    struct Counters {
+        connections_accepted: AtomicUsize,
+        active_connections: AtomicUsize,
+        requests: AtomicUsize,
+        responses: [AtomicUsize; 5],
+    }
+
+    /// Handle used for reading the counters of the server.
+    ///
+    /// ```rust
+    /// use rusty_web::server::{Config, MetricsHandle};
+    ///
+    /// let metrics = MetricsHandle::new();
+    /// let config = Config {
+    ///     metrics: Some(metrics.clone()),
+    ///     ..Config::default()
+    /// };
+    ///
+    /// // Call `metrics.metrics()` from another thread while the server is running
+    /// assert_eq!(0, metrics.metrics().requests);
+    /// ```
+    #[derive(Clone, Default)]
+    pub struct MetricsHandle {
+        counters: Arc<Counters>,
+    }
+
+    impl MetricsHandle {
+        pub fn new() -> Self {
+            return Self::default();
+        }
+
+        /// Returns the current values of the counters
+        pub fn metrics(&self) -> Metrics {
+            let counters = &self.counters;
+            return Metrics {
+                connections_accepted: counters.connections_accepted.load(Ordering::Relaxed),
+                active_connections: counters.active_connections.load(Ordering::Relaxed),
+                requests: counters.requests.load(Ordering::Relaxed),
+                responses: counters.responses.each_ref().map(|count| count.load(Ordering::Relaxed)),
+            };
+        }
+
+        fn connection_opened(&self) {
+            self.counters.connections_accepted.fetch_add(1, Ordering::Relaxed);
+            self.counters.active_connections.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn connection_closed(&self) {
+            self.counters.active_connections.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        pub(crate) fn request_received(&self) {
+            self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        }
+
+        pub(crate) fn response_sent(&self, status: usize) {
+            // Status code is public, so it may be outside the valid range
+            let class_index = (status / 100).checked_sub(1);
+            if let Some(count) = class_index.and_then(|index| self.counters.responses.get(index)) {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the response body and the content type for the error responses generated by the
+    /// server such as `404 Not Found`.
+    pub type ErrorRenderer = fn(usize, &Request) -> (String, String);
+
+    /// Renders the error as plain html text. Example: `404 NOT FOUND`
+    pub fn default_error_renderer(status: usize, _request: &Request) -> (String, String) {
+        let status_text = Status::status_text(status).unwrap_or("Custom Status".to_string());
+        let body = format!("{} {}", status, status_text.to_uppercase());
+        return (body, "text/html".to_string());
+    }
+
+    /// Decides how the requests which can't be parsed are handled
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum MalformedRequestPolicy {
+        /// Closes the connection without sending any response
+        #[default]
+        Close,
+        /// Sends the error response before closing the connection. For example:
+        /// `400 Bad Request` for the invalid request line.
+        Respond,
+    }
+
+    /// Server wide configuration
+    pub struct Config {
+        /// Maximum time allowed for reading the request body and running the view. The connection
+        /// is closed if the request takes longer than this. There is no limit by default.
+        pub request_timeout: Option<Duration>,
+        /// Used for all the error responses generated by the server
+        pub error_renderer: ErrorRenderer,
+        /// Handle used for stopping the server gracefully. Server runs forever if not set.
+        pub shutdown: Option<ShutdownHandle>,
+        /// Maximum time to wait for the active requests after shutdown is requested
+        pub shutdown_timeout: Duration,
+        /// Limits for decoding the request body sent with `Content-Encoding`
+        pub decode_limits: DecodeLimits,
+        /// Capacity of the buffer used for writing the response. Larger buffer reduces the
+        /// number of writes for large responses.
+        pub write_buffer_size: usize,
+        /// Path of the built-in health check which returns `200 OK` for `GET` and `HEAD`
+        /// requests. It is served before matching the paths. Disabled by default.
+        pub health_check_path: Option<String>,
+        /// Headers added to every response unless the view has already set the header
+        pub default_headers: Vec<(String, String)>,
+        /// Handling of the malformed request line and the too large request headers
+        pub malformed_request_policy: MalformedRequestPolicy,
+        /// Connection threads are named as `{prefix}-{connection id}`
+        pub thread_name_prefix: String,
+        /// Counters of the connections, requests and responses. Not collected if not set.
+        pub metrics: Option<MetricsHandle>,
+        /// Maximum number of the open connections. New connections are closed with
+        /// `503 Service Unavailable` once the limit is reached. There is no limit by default.
+        pub max_connections: Option<usize>,
+        /// Views used for the error responses by the status class. See `Config::error_handler`.
+        pub error_views: HashMap<usize, fn(Request, Response)>,
+        /// Maximum size of the request line and headers. Larger requests are closed or responded
+        /// with `431 Request Header Fields Too Large` depending on `malformed_request_policy`.
+        pub max_header_size: usize,
+        /// Keeps the raw header lines of the request available through `Request::raw_headers`.
+        /// Useful for forwarding the headers verbatim. Disabled by default.
+        pub retain_raw_headers: bool,
+        /// Sends the HTML listing of the directory passed to `Response::send_file` if it doesn't
+        /// have `index.html`. Disabled by default, so the directory contents are not exposed.
+        pub auto_index: bool,
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            return Self {
+                request_timeout: None,
+                error_renderer: default_error_renderer,
+                shutdown: None,
+                shutdown_timeout: Duration::from_secs(30),
+                decode_limits: DecodeLimits::default(),
+                write_buffer_size: 8 * 1024, // 8 KiB
+                health_check_path: None,
+                default_headers: Vec::new(),
+                malformed_request_policy: MalformedRequestPolicy::default(),
+                thread_name_prefix: "rusty-web-worker".to_string(),
+                metrics: None,
+                max_connections: None,
+                error_views: HashMap::new(),
+                retain_raw_headers: false,
+                auto_index: false,
+                max_header_size: 1024 * 1024, // 1 MiB
+            };
+        }
+    }
+
+    impl Config {
+        /// Serves the error responses of the status class using the view instead of the error
+        /// renderer. For example: `5` for all `5xx` errors. Status of the error is already set in
+        /// the response passed to the view. The error renderer is used if the view doesn't send
+        /// the response.
+        pub fn error_handler(&mut self, status_class: usize, view: fn(Request, Response)) -> &mut Self {
+            self.error_views.insert(status_class, view);
+            return self;
         }
     }
 
@@ -100,91 +649,1204 @@ pub mod server {
         /// to use same connection. Make sure to set `accept_next` to false if request
         /// body is not read completely. It is passed to both Request struct.
         pub accept_next: AtomicBool,
+        pub config: Arc<Config>,
+        /// Incremented when the connection starts reading the next request. Responses are only
+        /// sent for the current request, so the pipelined responses are written in order.
+        pub request_sequence: AtomicUsize,
+        /// Sequence of the last request whose response is sent
+        pub response_sequence: AtomicUsize,
+        /// True while the request is being served. Idle connections are closed first on shutdown.
+        pub serving: AtomicBool,
+        /// Bytes read after the current request which belong to the next pipelined request
+        pending_bytes: Mutex<Vec<u8>>,
     }
 
     impl Context {
+        pub fn new(config: Arc<Config>) -> Self {
+            return Self {
+                accept_next: AtomicBool::new(true),
+                config,
+                request_sequence: AtomicUsize::new(0),
+                response_sequence: AtomicUsize::new(0),
+                serving: AtomicBool::new(false),
+                pending_bytes: Mutex::new(Vec::new()),
+            };
+        }
+
+        /// Returns true if the request with the given sequence is the one being served
+        pub fn is_current_request(&self, sequence: usize) -> bool {
+            return self.request_sequence.load(Ordering::Relaxed) == sequence;
+        }
+
+        /// Returns true if the response of the request with the given sequence is sent
+        pub fn is_response_sent(&self, sequence: usize) -> bool {
+            return self.response_sequence.load(Ordering::Relaxed) == sequence;
+        }
+
         pub fn dont_wait(&self) {
             self.accept_next.store(false, Ordering::Relaxed);
         }
-    }
 
-    fn serve_client(stream: TcpStream, paths: Arc<RwLock<Paths>>) {
-        let context = Context {
-            accept_next: AtomicBool::new(true),
-        };
-
-        let context_ref = Arc::new(context);
+        /// Keeps the bytes read after the current request for the next pipelined request
+        pub(crate) fn set_pending_bytes(&self, bytes: Vec<u8>) {
+            *self.pending_bytes.lock().unwrap() = bytes;
+        }
+    }
 
-        while context_ref.accept_next.load(Ordering::Relaxed) {
-            let stream = stream.try_clone().expect("Error cloning stream");
-            decode_request(stream, paths.clone(), context_ref.clone());
+    /// Requests of the connection are served one at a time. Pipelined requests are read only after
+    /// the view of the previous request returns, so the responses are sent in the request order.
+    /// The stream is shared by all the requests of the connection instead of being duplicated.
+    pub(crate) fn serve_client<S: Into<Arc<TcpStream>>>(stream: S, paths: Arc<RwLock<Paths>>, context: Arc<Context>) {
+        let stream = stream.into();
+        while context.accept_next.load(Ordering::Relaxed) {
+            decode_shared_request(stream.clone(), paths.clone(), context.clone());
+            context.serving.store(false, Ordering::Relaxed);
         }
     }
 
-    pub fn decode_request(mut stream: TcpStream, paths: Arc<RwLock<Paths>>,
+    pub fn decode_request(stream: TcpStream, paths: Arc<RwLock<Paths>>,
                           context: Arc<Context>) {
+        decode_shared_request(Arc::new(stream), paths, context);
+    }
+
+    /// Same as `decode_request` but the stream is shared with the other requests of the connection
+    fn decode_shared_request(stream: Arc<TcpStream>, paths: Arc<RwLock<Paths>>, context: Arc<Context>) {
         let mut header_start = String::new();
-        let mut partial_body_bytes = Vec::new();
+        let mut partial_body_bytes = std::mem::take(&mut *context.pending_bytes.lock().unwrap());
 
-        const MAX_HEADER_SIZE: usize = 1024 * 1024; // 1 MiB
-        let headers_result = extract_headers(
-            &mut stream,
-            &mut header_start,
+        // Responses of the previous request can't be sent once the next request is started
+        context.request_sequence.fetch_add(1, Ordering::Relaxed);
+
+        let header_block_result = read_header_block(
+            &mut stream.as_ref(),
             &mut partial_body_bytes,
-            MAX_HEADER_SIZE,
+            context.config.max_header_size,
         );
 
-        if !headers_result.is_ok() {
+        if let Err(error) = header_block_result {
             context.accept_next.store(false, Ordering::Relaxed);
+            if let RequestHeaderError::MaxSizeExceed = error {
+                serve_malformed_request(stream, context, HashMap::new(), Status::RequestHeaderFieldsTooLarge);
+            }
             return;
         }
 
-        let headers = headers_result.unwrap();
+        let header_block = header_block_result.unwrap();
+        let mut headers = parse_header_block(&header_block, &mut header_start);
+        context.serving.store(true, Ordering::Relaxed);
+        if let Some(metrics) = &context.config.metrics {
+            metrics.request_received();
+        }
 
         let request_info = parse_request_method_header(&header_start.as_str());
         if !request_info.is_some() {
             context.accept_next.store(false, Ordering::Relaxed);
-            let _ = stream.shutdown(Shutdown::Both);
+            serve_malformed_request(stream, context, headers, Status::BadRequest);
             return;
         }
 
         let (method, raw_path) = request_info.unwrap();
 
+        // Body of the request can't be found, so the connection can't be reused
+        if !is_valid_content_length(&headers) {
+            context.accept_next.store(false, Ordering::Relaxed);
+            headers.remove("Content-Length");
+            serve_malformed_request(stream, context, headers, Status::BadRequest);
+            return;
+        }
+
+        // Closes the connection if the request is not completed in time.
+        // Watchdog stops when the sender is dropped at the end of this function.
+        let mut _watchdog = None;
+        if let Some(request_timeout) = context.config.request_timeout {
+            _watchdog = Some(start_watchdog(stream.clone(), request_timeout, context.clone()));
+        }
+
         // These states are shared among request and response
         let body_read = Arc::new(AtomicBool::from(false));
         let body_parsed = Arc::new(AtomicBool::from(false));
 
         let mut request = Request::new(context, stream, method, raw_path, headers,
                                        body_read.clone(), body_parsed.clone());
+        if let Some(version) = parse_request_version(&header_start) {
+            request.version = version;
+        }
+        if request.context.config.retain_raw_headers {
+            // Header lines after the request line
+            let raw_headers = header_block.split_once("\r\n").map(|(_, lines)| lines).unwrap_or("");
+            request.set_raw_headers(raw_headers.to_string());
+        }
         request.setup();
 
+        // Bytes after the body belong to the next pipelined request. Body length of chunked
+        // request is unknown, so the bytes are left to the body.
+        let body_length = if !request.has_body() {
+            Some(0)
+        } else if request.headers.contains_key("Transfer-Encoding") {
+            None
+        } else {
+            request.content_length()
+        };
+
+        if let Some(body_length) = body_length {
+            if partial_body_bytes.len() > body_length {
+                let next_request_bytes = partial_body_bytes.split_off(body_length);
+                request.context.set_pending_bytes(next_request_bytes);
+            }
+        }
+
         // Some bytes are read unintentionally from the body. Set read value in the struct.
         request.set_partial_body_bytes(partial_body_bytes);
 
-        let mut matched_view: Option<&SinglePath> = None;
+        // Only `100-continue` expectation is supported
+        let expectations = request.header_list("Expect");
+        if expectations.iter().any(|expectation| !expectation.eq_ignore_ascii_case("100-continue")) {
+            serve_expectation_failed(request);
+            return;
+        }
+
+        if let Some(health_check_path) = &request.context.config.health_check_path {
+            if request.pathname == *health_check_path && (request.method == "GET" || request.method == "HEAD") {
+                serve_health_check(request);
+                return;
+            }
+        }
+
+        // Asterisk-form target is only used for the server wide `OPTIONS` request
+        if request.raw_path == "*" {
+            if request.method == "OPTIONS" {
+                serve_server_options(request, &paths.read().unwrap());
+            } else {
+                Response::new(request).send_error(Status::BadRequest);
+            }
+            return;
+        }
 
         let binding = paths.read().unwrap();
-        for path in binding.iter() {
-            if request.pathname == path.name {
-                matched_view = Some(&path);
+        let (matched_view, match_result) = resolve_path(&binding, &request.method, &request.pathname);
+
+        match (matched_view, match_result) {
+            (Some(view), MatchResult::Found(path_params)) => {
+                request.matched_route = Some(view.name.clone());
+                request.path_params = path_params;
+
+                let panic_request = request.clone();
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    serve_page(request, view);
+                }));
+
+                if result.is_err() {
+                    serve_panic(panic_request);
+                }
+            }
+
+            (_, MatchResult::MethodNotAllowed(methods)) => {
+                serve_method_not_allowed(request, &methods);
+            }
+
+            _ => {
+                serve_not_found(request);
             }
         }
+    }
 
-        if let Some(view) = matched_view {
-            serve_page(request, view);
-        } else {
-            serve_not_found(request);
+    /// Sends `500 Internal Server Error` if the view panicked before sending the response.
+    /// The connection is closed as the request body may be partially read.
+    fn serve_panic(request: Request) {
+        eprintln!("View panicked while serving {} {}", request.method, request.pathname);
+        request.context.dont_wait();
+
+        if request.context.is_response_sent(request.sequence) {
+            let _ = request.stream.shutdown(Shutdown::Both);
+            return;
+        }
+
+        let mut response = Response::new(request);
+        response.send_error(Status::InternalServerError);
+    }
+
+    /// Shuts down the stream if nothing is sent to the returned sender before the timeout.
+    fn start_watchdog(stream: Arc<TcpStream>, timeout: Duration, context: Arc<Context>) -> Sender<()> {
+        let (sender, receiver) = channel::<()>();
+        spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(timeout) {
+                eprintln!("Request timed out");
+                context.dont_wait();
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        });
+
+        return sender;
+    }
+
+    /// Restores the read timeout of the connection after the view, even if the view panics. Read
+    /// timeout of the route doesn't apply to the next requests of the keep-alive connection.
+    struct ReadTimeoutGuard {
+        stream: Arc<TcpStream>,
+        timeout: Option<Duration>,
+    }
+
+    impl Drop for ReadTimeoutGuard {
+        fn drop(&mut self) {
+            if self.stream.set_read_timeout(self.timeout).is_err() {
+                eprintln!("Failed to restore read timeout");
+            }
         }
     }
 
-    fn serve_page(request: Request, matched_path: &SinglePath) {
+    fn serve_page(mut request: Request, matched_path: &SinglePath) {
+        let mut _timeout_guard = None;
+        if matched_path.config.read_timeout.is_some() {
+            if let Ok(timeout) = request.stream.read_timeout() {
+                _timeout_guard = Some(ReadTimeoutGuard { stream: request.stream.clone(), timeout });
+            }
+        }
+
+        request.apply_route_config(&matched_path.config);
+
+        for middleware in matched_path.middlewares.iter() {
+            if let Flow::Halt = middleware(&mut request) {
+                return;
+            }
+        }
+
         let response = Response::new(request.clone());
-        (matched_path.view)(request, response);
+
+        match &matched_path.view {
+            View::Plain(view) => view(request, response),
+            View::Result(view) => serve_result(request, response, *view),
+        }
+    }
+
+    /// Sends the response returned by the view. Error is sent using the error renderer.
+    fn serve_result(request: Request, response: Response, result_view: ResultView) {
+        let error_response = Response::new(request.clone());
+
+        match result_view(request, response) {
+            Ok(mut response) => {
+                if response.is_sent() {
+                    return;
+                }
+
+                if response.status.is_none() {
+                    eprintln!("View returned the response without content");
+                    response.send_error(Status::InternalServerError);
+                    return;
+                }
+
+                response.send();
+            }
+
+            Err(error) => {
+                eprintln!("View error: {}", error);
+                let mut response = error_response;
+                response.send_error(error.status);
+            }
+        }
     }
 
     fn serve_not_found(request: Request) {
         let mut response = Response::new(request);
-        response.html(404, "404 NOT FOUND".to_string());
-        response.send();
+        response.send_error(Status::NotFound);
+    }
+
+    /// Sends `405 Method Not Allowed` with the methods served for the pathname in `Allow` header
+    fn serve_method_not_allowed(request: Request, methods: &[String]) {
+        let mut response = Response::new(request);
+        response.set_header("Allow", &methods.join(", "));
+        response.send_error(Status::MethodNotAllowed);
+    }
+
+    /// Sends the error response or just closes the connection based on the
+    /// `malformed_request_policy` of the server config
+    fn serve_malformed_request(stream: Arc<TcpStream>, context: Arc<Context>, headers: Headers, status: Status) {
+        if context.config.malformed_request_policy == MalformedRequestPolicy::Close {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+
+        // Request line is unknown, so the request is served as an empty method and path
+        let request = Request::new(context, stream, String::new(), String::new(), headers,
+                                   Arc::new(AtomicBool::from(false)), Arc::new(AtomicBool::from(false)));
+        let mut response = Response::new(request);
+        response.send_error(status);
+    }
+
+    /// Methods listed for `OPTIONS *` if any path serves all the methods
+    const ALL_METHODS: [&str; 7] = ["DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST", "PUT"];
+
+    /// Responds to `OPTIONS *` with the methods served by any of the paths in `Allow` header
+    fn serve_server_options(request: Request, paths: &Paths) {
+        let mut methods = BTreeSet::from(["OPTIONS".to_string()]);
+        for path in paths.iter() {
+            match &path.method {
+                Some(method) => {
+                    // `GET` paths serve `HEAD` requests too
+                    if method.eq_ignore_ascii_case("GET") {
+                        methods.insert("HEAD".to_string());
+                    }
+                    methods.insert(method.to_string());
+                }
+
+                None => {
+                    methods.extend(ALL_METHODS.map(|method| method.to_string()));
+                }
+            }
+        }
+
+        let allow = methods.into_iter().collect::<Vec<String>>().join(", ");
+        let mut response = Response::new(request);
+        response.set_header("Allow", &allow);
+        response.bytes(Status::Ok, Vec::new()).send();
+    }
+
+    fn serve_health_check(request: Request) {
+        let mut response = Response::new(request);
+        response.html(Status::Ok, "OK".to_string());
+        response.set_content_type("text/plain");
+        response.send();
+    }
+
+    /// Sends `417 Expectation Failed`. The connection is closed as the body is not read.
+    fn serve_expectation_failed(request: Request) {
+        request.context.dont_wait();
+        let mut response = Response::new(request);
+        response.send_error(Status::ExpectationFailed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread::{sleep, spawn};
+    use std::time::{Duration, Instant};
+    use std::sync::Arc;
+    use crate::handler::{HandlerError, HandlerResult};
+    use crate::paths::{Flow, Path, Paths, RouteConfig, View};
+    use crate::request::form::MapFirstStringMethod;
+    use crate::request::Request;
+    use crate::response::Response;
+    use crate::router::Router;
+    use std::sync::mpsc::channel;
+    use crate::server::{bind_address, bind_dual_stack, Config, listen_connections_with_config, MalformedRequestPolicy, Metrics, MetricsHandle,
+                        next_accept_backoff, run_server_with_listener, ShutdownHandle};
+    use crate::status::Status;
+
+    fn home(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "Home Page".to_string()).send();
+    }
+
+    /// Starts the server in the background and returns the assigned address
+    fn start_server(paths: Paths) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        spawn(move || {
+            run_server_with_listener(listener, paths);
+        });
+
+        return address;
+    }
+
+    fn start_server_with_config(paths: Paths, config: Config) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        spawn(move || {
+            listen_connections_with_config(listener, paths, config);
+        });
+
+        return address;
+    }
+
+    fn send_raw(address: &str, raw_request: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(raw_request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        return response;
+    }
+
+    #[test]
+    fn test_run_server_with_listener() {
+        let paths: Paths = vec![
+            Path::new("/", home),
+        ];
+
+        let address = start_server(paths);
+        assert_eq!(false, address.ends_with(":0"));
+
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.ends_with("Home Page"));
+    }
+
+    fn client_ip(request: Request, mut response: Response) {
+        let client_ip = request.client_ip().unwrap();
+        response.html(Status::Ok, client_ip.to_string()).send();
+    }
+
+    #[test]
+    fn test_bind_ipv6() {
+        let paths: Paths = vec![
+            Path::new("/", client_ip),
+        ];
+
+        let listener = bind_address("[::1]:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        assert_eq!(true, address.is_ipv6());
+
+        spawn(move || {
+            run_server_with_listener(listener, paths);
+        });
+
+        let response = send_raw(&address.to_string(), "GET / HTTP/1.1\r\nHost: [::1]\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\n::1"));
+    }
+
+    #[test]
+    fn test_bind_dual_stack() {
+        assert_eq!(true, bind_address(":http").is_err());
+        assert_eq!(true, bind_address(":70000").is_err());
+
+        let paths: Paths = vec![
+            Path::new("/", client_ip),
+        ];
+
+        let listener = bind_address(":0").unwrap();
+        assert_eq!(true, listener.local_addr().unwrap().is_ipv6());
+        drop(listener);
+
+        let listener = bind_dual_stack(0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        spawn(move || {
+            run_server_with_listener(listener, paths);
+        });
+
+        let raw_request = "GET / HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let response = send_raw(&format!("127.0.0.1:{}", port), raw_request);
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        let response = send_raw(&format!("[::1]:{}", port), raw_request);
+        assert_eq!(true, response.ends_with("\r\n\r\n::1"));
+    }
+
+    fn slow(_request: Request, mut response: Response) {
+        sleep(Duration::from_millis(500));
+        response.html(Status::Ok, "Slow Page".to_string()).send();
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let paths: Paths = vec![
+            Path::new("/slow", slow),
+        ];
+
+        let config = Config {
+            request_timeout: Some(Duration::from_millis(100)),
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(paths, config);
+        let response = send_raw(&address, "GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert_eq!("", response);
+    }
+
+    #[test]
+    fn test_group_middleware() {
+        let mut router = Router::new();
+        router.add("GET", "/", home);
+        router.group("/admin", |group| {
+            group.middleware(Arc::new(|request: &mut Request| {
+                let mut response = Response::new(request.clone());
+                response.html(Status::Forbidden, "Forbidden".to_string()).send();
+                return Flow::Halt;
+            }));
+            group.add("GET", "/", home);
+        });
+
+        let address = start_server(router.into_paths());
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        let response = send_raw(&address, "GET /admin/ HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    }
+
+    fn user_detail(request: Request, mut response: Response) {
+        let text = format!("{} {}", request.matched_route().unwrap(), request.path_param("id").unwrap());
+        response.html(Status::Ok, text).send();
+    }
+
+    #[test]
+    fn test_matched_route() {
+        let mut router = Router::new();
+        router.add("GET", "/users/:id", user_detail);
+
+        let address = start_server(router.into_paths());
+        let response = send_raw(&address, "GET /users/42 HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.ends_with("\r\n\r\n/users/:id 42"));
+    }
+
+    fn upload(mut request: Request, mut response: Response) {
+        if request.body().is_some() {
+            response.html(Status::Ok, "Uploaded".to_string()).send();
+        } else {
+            response.html(Status::PayloadTooLarge, "Too Large".to_string()).send();
+        }
+    }
+
+    #[test]
+    fn test_route_config_body_limit() {
+        let small = RouteConfig {
+            max_body_size: Some(10),
+            ..RouteConfig::default()
+        };
+
+        let large = RouteConfig {
+            max_body_size: Some(1000),
+            ..RouteConfig::default()
+        };
+
+        let mut router = Router::new();
+        router.add_path(Path::with_method("POST", "/small", upload as fn(Request, Response)).with_config(small));
+        router.add_path(Path::with_method("POST", "/large", upload as fn(Request, Response)).with_config(large));
+
+        let address = start_server(router.into_paths());
+        let body = "a".repeat(100);
+
+        let raw_request = format!("POST /small HTTP/1.1\r\nContent-Length: 100\r\n\r\n{}", body);
+        let response = send_raw(&address, &raw_request);
+        assert_eq!(true, response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        let raw_request = format!("POST /large HTTP/1.1\r\nContent-Length: 100\r\n\r\n{}", body);
+        let response = send_raw(&address, &raw_request);
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_route_read_timeout_restored() {
+        let short = RouteConfig {
+            read_timeout: Some(Duration::from_millis(50)),
+            ..RouteConfig::default()
+        };
+
+        let mut router = Router::new();
+        router.add_path(Path::with_method("GET", "/api", home as fn(Request, Response)).with_config(short));
+        router.add("GET", "/", home);
+
+        let address = start_server(router.into_paths());
+        let mut stream = TcpStream::connect(&address).unwrap();
+        stream.write_all(b"GET /api HTTP/1.1\r\nConnection: keep-alive\r\n\r\n").unwrap();
+
+        let mut buffer = [0u8; 1024];
+        let read_size = stream.read(&mut buffer).unwrap();
+        assert_eq!(true, buffer[..read_size].starts_with(b"HTTP/1.1 200 OK\r\n"));
+
+        // Idle time longer than the read timeout of the previous route
+        sleep(Duration::from_millis(200));
+        stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    fn json_error(status: usize, request: &Request) -> (String, String) {
+        let body = format!("{{\"status\": {}, \"path\": \"{}\"}}", status, request.pathname);
+        return (body, "application/json".to_string());
+    }
+
+    #[test]
+    fn test_error_renderer() {
+        let paths: Paths = vec![
+            Path::new("/", home),
+        ];
+
+        let address = start_server(paths);
+        let response = send_raw(&address, "GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert_eq!(true, response.ends_with("404 NOT FOUND"));
+
+        let paths: Paths = vec![
+            Path::new("/", home),
+        ];
+
+        let config = Config {
+            error_renderer: json_error,
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(paths, config);
+        let response = send_raw(&address, "GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert_eq!(true, response.contains("Content-Type: application/json\r\n"));
+        assert_eq!(true, response.ends_with("{\"status\": 404, \"path\": \"/missing\"}"));
+    }
+
+    fn first(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "First".to_string()).send();
+    }
+
+    fn echo_body(mut request: Request, mut response: Response) {
+        let body = request.body();
+        let content = std::fs::read_to_string(body.unwrap().path()).unwrap();
+        response.html(Status::Ok, format!("Upload {}", content)).send();
+    }
+
+    fn last(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "Last".to_string()).send();
+    }
+
+    #[test]
+    fn test_pipelined_requests() {
+        let paths: Paths = vec![
+            Path::new("/first", first),
+            Path::new("/upload", echo_body),
+            Path::new("/last", last),
+        ];
+
+        let address = start_server(paths);
+
+        // All the requests are sent at once before reading any response
+        let raw_request = "GET /first HTTP/1.1\r\nConnection: keep-alive\r\n\r\n\
+                           POST /upload HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\nhello\
+                           GET /last HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let response = send_raw(&address, raw_request);
+
+        let first_index = response.find("First").unwrap();
+        let upload_index = response.find("Upload hello").unwrap();
+        let last_index = response.find("Last").unwrap();
+        assert_eq!(true, first_index < upload_index && upload_index < last_index);
+        assert_eq!(3, response.matches("HTTP/1.1 200 OK\r\n").count());
+    }
+
+    /// Responds with the file descriptors of the request and the response streams
+    #[cfg(unix)]
+    fn stream_fd(request: Request, mut response: Response) {
+        use std::os::unix::io::AsRawFd;
+        let fds = format!("fd={}/{};", request.stream.as_raw_fd(), response.request.stream.as_raw_fd());
+        response.html(Status::Ok, fds).send();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_keep_alive_shares_stream() {
+        let address = start_server(vec![Path::new("/", stream_fd)]);
+
+        let mut raw_request = "GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n".repeat(100);
+        raw_request.push_str("GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let response = send_raw(&address, &raw_request);
+
+        // Request, response and every request of the connection use the same file descriptor
+        // instead of a duplicate
+        let fds: Vec<&str> = response.split("fd=").skip(1)
+            .map(|rest| rest.split(';').next().unwrap())
+            .collect();
+        assert_eq!(101, fds.len());
+        assert_eq!(true, fds.iter().all(|fd| *fd == fds[0]));
+        let (request_fd, response_fd) = fds[0].split_once('/').unwrap();
+        assert_eq!(request_fd, response_fd);
+    }
+
+    #[test]
+    fn test_graceful_shutdown() {
+        let paths: Paths = vec![
+            Path::new("/", home),
+            Path::new("/slow", slow),
+        ];
+
+        let shutdown = ShutdownHandle::new();
+        let config = Config {
+            shutdown: Some(shutdown.clone()),
+            ..Config::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let (sender, receiver) = channel();
+        spawn(move || {
+            listen_connections_with_config(listener, paths, config);
+            sender.send(()).unwrap();
+        });
+
+        // Idle keep-alive connection must not block the shutdown
+        let mut idle = TcpStream::connect(&address).unwrap();
+        idle.write_all(b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n").unwrap();
+        let mut buffer = [0u8; 1024];
+        assert_eq!(true, idle.read(&mut buffer).unwrap() > 0);
+
+        // Active request is completed after the shutdown is requested
+        let mut active = TcpStream::connect(&address).unwrap();
+        active.write_all(b"GET /slow HTTP/1.1\r\nConnection: keep-alive\r\n\r\n").unwrap();
+        sleep(Duration::from_millis(100));
+        shutdown.shutdown();
+
+        let mut response = String::new();
+        active.read_to_string(&mut response).unwrap();
+        assert_eq!(true, response.ends_with("Slow Page"));
+        assert_eq!(false, response.contains("Connection: keep-alive"));
+
+        assert_eq!(true, receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert_eq!(true, TcpStream::connect(&address).is_err());
+    }
+
+    /// Runs the server in a child process and stops it with SIGTERM
+    #[cfg(all(unix, feature = "signals"))]
+    #[test]
+    fn test_shutdown_on_signal() {
+        use std::io::{BufRead, BufReader};
+        use std::process::{Command, Stdio};
+
+        if std::env::var("RUSTY_WEB_SIGNAL_CHILD").is_ok() {
+            let paths: Paths = vec![
+                Path::new("/", home),
+            ];
+
+            let shutdown = ShutdownHandle::new();
+            shutdown.shutdown_on_signals().unwrap();
+
+            let config = Config {
+                shutdown: Some(shutdown),
+                ..Config::default()
+            };
+
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            println!("Address: {}", listener.local_addr().unwrap());
+            listen_connections_with_config(listener, paths, config);
+            return;
+        }
+
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args(["test::test_shutdown_on_signal", "--exact", "--nocapture", "--test-threads=1"])
+            .env("RUSTY_WEB_SIGNAL_CHILD", "1")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // Output of the child is read until it exits, otherwise logging fails with broken pipe
+        let stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = channel();
+        spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                // Test harness may print the test name on the same line
+                if let Some((_, address)) = line.unwrap().split_once("Address: ") {
+                    sender.send(address.to_string()).unwrap();
+                }
+            }
+        });
+
+        let address = receiver.recv_timeout(Duration::from_secs(30)).unwrap();
+
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.ends_with("Home Page"));
+
+        let status = Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+        assert_eq!(true, status.success());
+        assert_eq!(true, child.wait().unwrap().success());
+    }
+
+    fn result_view(request: Request, mut response: Response) -> HandlerResult {
+        let name = request.query_params.single("name")?;
+        if name == "admin" {
+            return Err(HandlerError::not_found("Hidden user"));
+        }
+
+        response.html(Status::Ok, format!("Hello {}", name));
+        return Ok(response);
+    }
+
+    #[test]
+    fn test_result_view() {
+        let mut router = Router::new();
+        router.add_result("GET", "/hello", result_view);
+        assert_eq!(true, matches!(router.paths()[0].view, View::Result(_)));
+        assert_eq!(true, matches!(Path::<View>::new("/", home).view, View::Plain(_)));
+
+        let config = Config {
+            error_renderer: json_error,
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(router.into_paths(), config);
+
+        let response = send_raw(&address, "GET /hello?name=John HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.ends_with("Hello John"));
+
+        // Error is sent using the error renderer
+        let response = send_raw(&address, "GET /hello?name=admin HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert_eq!(true, response.ends_with("{\"status\": 404, \"path\": \"/hello\"}"));
+
+        // Form error is converted to bad request
+        let response = send_raw(&address, "GET /hello HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    fn panic_view(_request: Request, _response: Response) {
+        panic!("View failed");
+    }
+
+    #[test]
+    fn test_view_panic() {
+        let paths: Paths = vec![
+            Path::new("/", home),
+            Path::new("/panic", panic_view),
+        ];
+
+        let address = start_server(paths);
+
+        let mut stream = TcpStream::connect(&address).unwrap();
+        stream.write_all(b"GET /panic HTTP/1.1\r\nConnection: keep-alive\r\n\r\n").unwrap();
+
+        // Connection is closed after the error response
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(true, response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert_eq!(true, response.ends_with("500 INTERNAL SERVER ERROR"));
+
+        // Server keeps serving other requests
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.ends_with("Home Page"));
+    }
+
+    #[test]
+    fn test_unknown_expectation() {
+        let paths: Paths = vec![Path::new("/", home)];
+        let address = start_server(paths);
+
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nExpect: something-weird\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 417 Expectation Failed\r\n"));
+
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nExpect: 100-continue\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    fn logout(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "Logged out".to_string()).close_connection().send();
+    }
+
+    #[test]
+    fn test_close_connection() {
+        let paths: Paths = vec![Path::new("/logout", logout), Path::new("/", home)];
+        let address = start_server(paths);
+
+        // Second request is not served as the connection is closed after the first response
+        let response = send_raw(&address, "GET /logout HTTP/1.1\r\nConnection: keep-alive\r\n\r\n\
+                                           GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n");
+        assert_eq!(true, response.contains("Connection: close\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\nLogged out"));
+        assert_eq!(1, response.matches("HTTP/1.1").count());
+    }
+
+    #[test]
+    fn test_ignored_body() {
+        let paths: Paths = vec![Path::new("/", home)];
+        let address = start_server(paths);
+
+        // Body of the first request is not read by the view
+        let mut stream = TcpStream::connect(&address).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 11\r\n\r\nhello").unwrap();
+        sleep(Duration::from_millis(100));
+        stream.write_all(b" world").unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(2, response.matches("HTTP/1.1 200 OK\r\n").count());
+        assert_eq!(true, response.contains("Connection: keep-alive\r\n"));
+    }
+
+    #[test]
+    fn test_health_check() {
+        let config = Config {
+            health_check_path: Some("/healthz".to_string()),
+            ..Config::default()
+        };
+
+        let paths: Paths = vec![];
+        let address = start_server_with_config(paths, config);
+
+        let response = send_raw(&address, "GET /healthz HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\nOK"));
+
+        let response = send_raw(&address, "POST /healthz HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        // Disabled by default
+        let address = start_server(vec![]);
+        let response = send_raw(&address, "GET /healthz HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    fn raw_headers(request: Request, mut response: Response) {
+        let raw_headers = request.raw_headers().unwrap_or("None").to_string();
+        response.html(Status::Ok, raw_headers).send();
+    }
+
+    #[test]
+    fn test_raw_headers() {
+        let config = Config {
+            retain_raw_headers: true,
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", raw_headers)], config);
+        let raw_block = "host: localhost\r\nX-Custom:  spaced value \r\nX-Custom: second\r\nConnection: close";
+        let response = send_raw(&address, &format!("GET / HTTP/1.1\r\n{}\r\n\r\n", raw_block));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(raw_block, body);
+
+        // Disabled by default
+        let address = start_server(vec![Path::new("/", raw_headers)]);
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.ends_with("\r\n\r\nNone"));
+    }
+
+    fn original_case_headers(request: Request, mut response: Response) {
+        let lines: Vec<String> = request.headers_original_case()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        response.html(Status::Ok, lines.join(",")).send();
+    }
+
+    #[test]
+    fn test_headers_original_case() {
+        let config = Config {
+            retain_raw_headers: true,
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", original_case_headers)], config);
+        let raw_request = "GET / HTTP/1.1\r\nx-CUSTOM-header: a\r\nHOST: localhost\r\nx-CUSTOM-header: b\r\n\
+                           connection: close\r\n\r\n";
+        let response = send_raw(&address, raw_request);
+        assert_eq!(true, response.ends_with("\r\n\r\nx-CUSTOM-header=a,HOST=localhost,x-CUSTOM-header=b,connection=close"));
+
+        // Casing is kept without the raw headers but the order is unspecified
+        let address = start_server(vec![Path::new("/", original_case_headers)]);
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nx-CUSTOM-header: a\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.contains("x-CUSTOM-header=a"));
+        assert_eq!(true, response.contains("Connection=close"));
+    }
+
+    fn request_id_header(response: &str) -> Option<&str> {
+        return response.split("\r\n").find_map(|line| line.strip_prefix("X-Request-Id: "));
+    }
+
+    #[test]
+    fn test_request_id() {
+        let address = start_server(vec![Path::new("/", home)]);
+
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let first_id = request_id_header(&response).unwrap().to_string();
+        assert_eq!(false, first_id.is_empty());
+
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(false, request_id_header(&response).unwrap() == first_id);
+
+        // Incoming id is preserved
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nX-Request-Id: abc-123\r\nConnection: close\r\n\r\n");
+        assert_eq!(Some("abc-123"), request_id_header(&response));
+    }
+
+    #[test]
+    fn test_malformed_request_policy() {
+        let address = start_server(vec![Path::new("/", home)]);
+        let response = send_raw(&address, "MALFORMED\r\n\r\n");
+        assert_eq!("", response);
+
+        let config = Config {
+            malformed_request_policy: MalformedRequestPolicy::Respond,
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", home)], config);
+        let response = send_raw(&address, "MALFORMED\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert_eq!(true, response.contains("Connection: close\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\n400 BAD REQUEST"));
+    }
+
+    #[test]
+    fn test_invalid_content_length() {
+        let raw_request = "POST / HTTP/1.1\r\nContent-Length: abc\r\nConnection: keep-alive\r\n\r\nhello";
+
+        let address = start_server(vec![Path::new("/", home)]);
+        let response = send_raw(&address, raw_request);
+        assert_eq!("", response);
+
+        let config = Config {
+            malformed_request_policy: MalformedRequestPolicy::Respond,
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", home)], config);
+        let response = send_raw(&address, raw_request);
+        assert_eq!(true, response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert_eq!(true, response.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn test_max_header_size() {
+        let config = Config {
+            max_header_size: 256,
+            malformed_request_policy: MalformedRequestPolicy::Respond,
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", home)], config);
+        let raw_request = format!("GET / HTTP/1.1\r\nCookie: {}\r\nConnection: close\r\n\r\n", "a".repeat(300));
+        let response = send_raw(&address, &raw_request);
+        assert_eq!(true, response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+
+        let raw_request = format!("GET / HTTP/1.1\r\nCookie: {}\r\nConnection: close\r\n\r\n", "a".repeat(100));
+        let response = send_raw(&address, &raw_request);
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        // Larger headers are allowed by default
+        let address = start_server(vec![Path::new("/", home)]);
+        let raw_request = format!("GET / HTTP/1.1\r\nCookie: {}\r\nConnection: close\r\n\r\n", "a".repeat(8000));
+        let response = send_raw(&address, &raw_request);
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    fn thread_name(_request: Request, mut response: Response) {
+        let name = std::thread::current().name().unwrap_or("").to_string();
+        response.html(Status::Ok, name).send();
+    }
+
+    #[test]
+    fn test_thread_name() {
+        let address = start_server(vec![Path::new("/", thread_name)]);
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let (_, name) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(true, name.starts_with("rusty-web-worker-"));
+
+        let config = Config {
+            thread_name_prefix: "api".to_string(),
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", thread_name)], config);
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.ends_with("\r\n\r\napi-1"));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let metrics = MetricsHandle::new();
+        let config = Config {
+            metrics: Some(metrics.clone()),
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", home)], config);
+        send_raw(&address, "GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        send_raw(&address, "GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+        // Connection thread may still be exiting after the response is read
+        let started_at = Instant::now();
+        while metrics.metrics().active_connections > 0 && started_at.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(10));
+        }
+
+        let expected = Metrics {
+            connections_accepted: 2,
+            active_connections: 0,
+            requests: 3,
+            responses: [0, 2, 0, 1, 0],
+        };
+        assert_eq!(expected, metrics.metrics());
+    }
+
+    #[test]
+    fn test_server_options() {
+        let mut router = Router::new();
+        router.add("GET", "/", home);
+        router.add("POST", "/submit", home);
+
+        let address = start_server(router.into_paths());
+        let response = send_raw(&address, "OPTIONS * HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.contains("\r\nAllow: GET, HEAD, OPTIONS, POST\r\n"));
+        assert_eq!(true, response.contains("\r\nContent-Length: 0\r\n"));
+
+        let response = send_raw(&address, "GET * HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn test_accept_backoff() {
+        // Connection error doesn't affect the listener
+        let error = std::io::Error::from(std::io::ErrorKind::ConnectionAborted);
+        assert_eq!(Duration::ZERO, next_accept_backoff(&error, Duration::ZERO));
+
+        // Too many open files
+        let error = std::io::Error::from_raw_os_error(24);
+        let mut backoff = next_accept_backoff(&error, Duration::ZERO);
+        assert_eq!(Duration::from_millis(10), backoff);
+
+        for _ in 0..10 {
+            backoff = next_accept_backoff(&error, backoff);
+        }
+        assert_eq!(Duration::from_secs(1), backoff);
+    }
+
+    #[test]
+    fn test_max_connections() {
+        let config = Config {
+            max_connections: Some(1),
+            ..Config::default()
+        };
+
+        let address = start_server_with_config(vec![Path::new("/", home)], config);
+        let idle_connection = TcpStream::connect(&address).unwrap();
+
+        let response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+
+        // Connection is accepted again once the open connection is closed
+        drop(idle_connection);
+        let started_at = Instant::now();
+        let mut response = String::new();
+        while !response.starts_with("HTTP/1.1 200 OK") && started_at.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(10));
+            response = send_raw(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        }
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    fn crash(_request: Request, _response: Response) {
+        panic!("View crashed");
+    }
+
+    fn error_view(_request: Request, mut response: Response) {
+        let status = response.status.unwrap();
+        response.html(status, format!("Custom error {}", status)).send();
+    }
+
+    #[test]
+    fn test_error_handler() {
+        let mut config = Config::default();
+        config.error_handler(5, error_view);
+
+        let paths: Paths = vec![Path::new("/", home), Path::new("/crash", crash)];
+        let address = start_server_with_config(paths, config);
+
+        let response = send_raw(&address, "GET /crash HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\nCustom error 500"));
+
+        // Other status classes still use the error renderer
+        let response = send_raw(&address, "GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(true, response.ends_with("\r\n\r\n404 NOT FOUND"));
     }
 }
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::paths::{Flow, Middleware};
+use crate::request::Request;
+use crate::response::Response;
+use crate::status::Status;
+
+/// Number of clients after which the refilled buckets are removed
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+/// Tokens available to a single client
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Limits each client IP to `requests` per `window` using a token bucket. Exceeding requests are
+/// responded with `429 Too Many Requests` and `Retry-After` header.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use rusty_web::middleware::rate_limit;
+/// use rusty_web::router::Router;
+/// # use rusty_web::request::Request;
+/// # use rusty_web::response::Response;
+/// # use rusty_web::status::Status;
+/// # fn home(request: Request, mut response: Response) {
+/// #    response.html(Status::Ok, "Home Page".to_string()).send();
+/// # }
+///
+/// let mut router = Router::new();
+/// router.middleware(rate_limit(60, Duration::from_secs(60)));
+/// router.add("GET", "/", home);
+/// ```
+pub fn rate_limit(requests: u32, window: Duration) -> Middleware {
+    let buckets: Mutex<HashMap<IpAddr, Bucket>> = Mutex::new(HashMap::new());
+    let capacity = requests as f64;
+    // Tokens added per second
+    let refill_rate = capacity / window.as_secs_f64();
+
+    return Arc::new(move |request: &mut Request| {
+        let client_ip = match request.client_ip() {
+            Some(client_ip) => client_ip,
+            None => return Flow::Continue,
+        };
+
+        let now = Instant::now();
+        let mut buckets = buckets.lock().unwrap();
+
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < window);
+        }
+
+        let bucket = buckets.entry(client_ip).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_rate).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Flow::Continue;
+        }
+
+        let retry_after = ((1.0 - bucket.tokens) / refill_rate).ceil().max(1.0) as u64;
+        drop(buckets);
+
+        let mut response = Response::new(request.clone());
+        response.set_header("Retry-After", &retry_after.to_string());
+        response.html(Status::TooManyRequests, "Too Many Requests".to_string()).send();
+        return Flow::Halt;
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use crate::middleware::rate_limit;
+    use crate::paths::{Path, Paths, SinglePath};
+    use crate::request::Request;
+    use crate::response::Response;
+    use crate::status::Status;
+    use crate::testing::handle_raw;
+
+    fn home(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "Home Page".to_string()).send();
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut path: SinglePath = Path::new("/", home);
+        path.middlewares.push(rate_limit(2, Duration::from_secs(60)));
+        let paths: Paths = vec![path];
+
+        let raw_request = "GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n\
+                           GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n\
+                           GET / HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let response = String::from_utf8(handle_raw(raw_request.as_bytes(), paths)).unwrap();
+
+        assert_eq!(2, response.matches("HTTP/1.1 200 OK\r\n").count());
+        let (_, limited_response) = response.split_once("HTTP/1.1 429 Too Many Requests\r\n").unwrap();
+        assert_eq!(true, limited_response.contains("Retry-After: 30\r\n"));
+        assert_eq!(true, limited_response.ends_with("\r\n\r\nToo Many Requests"));
+    }
+}
@@ -1,11 +1,190 @@
 use std::collections::HashMap;
 
+/// Source of the request body bytes used by all the body parsers
+pub mod reader {
+    use std::error::Error;
+    use std::fmt::{Display, Formatter};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use crate::parser::body::BodyReadError;
+    use crate::parser::multipart::MultipartFormDataError;
+    use crate::parser::url_encoded::UrlEncodedFormDataError;
+
+    /// Reads the request body in chunks. The error must be convertible to the error of the parser
+    /// where the reader is used. Implement it with `ReadError` to use the same reader for all the
+    /// parsers.
+    pub trait StreamReader {
+        type Error;
+
+        fn get_chunk(&mut self) -> Result<Vec<u8>, Self::Error>;
+        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, Self::Error>;
+    }
+
+    /// Allows passing `&mut dyn StreamReader` to the parsers
+    impl<T: StreamReader + ?Sized> StreamReader for &mut T {
+        type Error = T::Error;
+
+        fn get_chunk(&mut self) -> Result<Vec<u8>, Self::Error> {
+            return (**self).get_chunk();
+        }
+
+        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, Self::Error> {
+            return (**self).get_exact(size);
+        }
+    }
+
+    /// Allows passing `Box<dyn StreamReader>` to the parsers
+    impl<T: StreamReader + ?Sized> StreamReader for Box<T> {
+        type Error = T::Error;
+
+        fn get_chunk(&mut self) -> Result<Vec<u8>, Self::Error> {
+            return (**self).get_chunk();
+        }
+
+        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, Self::Error> {
+            return (**self).get_exact(size);
+        }
+    }
+
+    /// Called with the number of body bytes read so far and the Content-Length
+    pub type ProgressCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+    /// Wraps the reader and reports the progress after every chunk read. Nothing is reported if
+    /// there is no callback. Reading stops with `ReadError::Cancelled` once the cancel flag is set.
+    pub struct ProgressReader<R: StreamReader> {
+        reader: R,
+        bytes_read: usize,
+        content_length: usize,
+        progress: Option<ProgressCallback>,
+        cancel: Option<Arc<AtomicBool>>,
+    }
+
+    impl<R: StreamReader> ProgressReader<R> {
+        /// `bytes_read` is the number of body bytes already read before this reader
+        pub fn new(reader: R, bytes_read: usize, content_length: usize, progress: Option<ProgressCallback>) -> Self {
+            return Self {
+                reader,
+                bytes_read,
+                content_length,
+                progress,
+                cancel: None,
+            };
+        }
+
+        /// Checks the flag before every chunk read and cancels the read when it is `true`
+        pub fn with_cancel_flag(mut self, cancel: Option<Arc<AtomicBool>>) -> Self {
+            self.cancel = cancel;
+            return self;
+        }
+
+        fn is_cancelled(&self) -> bool {
+            return match &self.cancel {
+                Some(cancel) => cancel.load(Ordering::Relaxed),
+                None => false,
+            };
+        }
+
+        fn report(&mut self, chunk: &[u8]) {
+            self.bytes_read += chunk.len();
+            if let Some(progress) = &self.progress {
+                progress(self.bytes_read, self.content_length);
+            }
+        }
+    }
+
+    impl<R: StreamReader<Error: From<ReadError>>> StreamReader for ProgressReader<R> {
+        type Error = R::Error;
+
+        fn get_chunk(&mut self) -> Result<Vec<u8>, Self::Error> {
+            if self.is_cancelled() {
+                return Err(ReadError::Cancelled.into());
+            }
+
+            let chunk = self.reader.get_chunk()?;
+            self.report(&chunk);
+            return Ok(chunk);
+        }
+
+        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, Self::Error> {
+            if self.is_cancelled() {
+                return Err(ReadError::Cancelled.into());
+            }
+
+            let chunk = self.reader.get_exact(size)?;
+            self.report(&chunk);
+            return Ok(chunk);
+        }
+    }
+
+    /// Reader error which is accepted by all the parsers
+    #[derive(Debug)]
+    pub enum ReadError {
+        /// Occurs if there is no more data left to read
+        BodyReadEnd,
+        /// Occurs if the body is larger than the limit of the reader
+        MaxSizeExceed,
+        /// Occurs if the read is cancelled with the cancel flag
+        Cancelled,
+        /// Occurs if error not fulfilled by above conditions. Example: client disconnected
+        Others(&'static str),
+    }
+
+    impl Display for ReadError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            return match self {
+                ReadError::BodyReadEnd => write!(f, "Body ended before it was fully read"),
+                ReadError::MaxSizeExceed => write!(f, "Maximum body size exceed"),
+                ReadError::Cancelled => write!(f, "Body read is cancelled"),
+                ReadError::Others(message) => write!(f, "{}", message),
+            };
+        }
+    }
+
+    impl Error for ReadError {}
+
+    impl From<ReadError> for BodyReadError {
+        fn from(error: ReadError) -> Self {
+            return match error {
+                ReadError::BodyReadEnd => BodyReadError::Others("Body ended before Content-Length"),
+                ReadError::MaxSizeExceed => BodyReadError::MaxBodySizeExceed,
+                ReadError::Cancelled => BodyReadError::Cancelled,
+                ReadError::Others(message) => BodyReadError::Others(message),
+            };
+        }
+    }
+
+    impl From<ReadError> for UrlEncodedFormDataError {
+        fn from(error: ReadError) -> Self {
+            return match error {
+                ReadError::BodyReadEnd => UrlEncodedFormDataError::BodyReadEnd,
+                ReadError::MaxSizeExceed => UrlEncodedFormDataError::MaxBodySizeExceed("Maximum body size exceed"),
+                ReadError::Cancelled => UrlEncodedFormDataError::Cancelled,
+                ReadError::Others(message) => UrlEncodedFormDataError::Others(message),
+            };
+        }
+    }
+
+    impl From<ReadError> for MultipartFormDataError {
+        fn from(error: ReadError) -> Self {
+            return match error {
+                ReadError::BodyReadEnd => MultipartFormDataError::BodyReadEnd,
+                ReadError::MaxSizeExceed => MultipartFormDataError::MaxBodySizeExceed("Maximum body size exceed"),
+                ReadError::Cancelled => MultipartFormDataError::Cancelled,
+                ReadError::Others(message) => MultipartFormDataError::Others(message),
+            };
+        }
+    }
+}
+
 pub mod body {
+    use std::error::Error;
+    use std::fmt::{Display, Formatter};
     use std::io::{Seek, SeekFrom, Write};
     use tempfile::NamedTempFile;
     use crate::headers;
     use crate::headers::Headers;
     use crate::parser::body::reader::StreamReader;
+    use crate::parser::content_encoding::DecodeError;
 
     pub struct Limits {
         pub max_body_size: usize,
@@ -16,30 +195,57 @@ pub mod body {
         MaxBodySizeExceed,
         ContentLengthMissing,
         BodyAlreadyRead,
+        /// Occurs if the body sent with `Content-Encoding` can't be decoded
+        Decode(DecodeError),
+        /// Occurs if `Transfer-Encoding` codings are not supported or the chunked body is invalid
+        InvalidTransferEncoding(&'static str),
+        /// Occurs if the body read is cancelled. See `Request::set_body_cancel_flag`.
+        Cancelled,
         Others(&'static str),
     }
 
+    impl Display for BodyReadError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            return match self {
+                BodyReadError::MaxBodySizeExceed => write!(f, "Maximum body size exceed"),
+                BodyReadError::ContentLengthMissing => write!(f, "Content-Length header is missing"),
+                BodyReadError::BodyAlreadyRead => write!(f, "Body is already read"),
+                BodyReadError::Decode(error) => write!(f, "Unable to decode body: {}", error),
+                BodyReadError::InvalidTransferEncoding(message) => write!(f, "{}", message),
+                BodyReadError::Cancelled => write!(f, "Body read is cancelled"),
+                BodyReadError::Others(message) => write!(f, "{}", message),
+            };
+        }
+    }
+
+    impl Error for BodyReadError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            return match self {
+                BodyReadError::Decode(error) => Some(error),
+                _ => None,
+            };
+        }
+    }
+
     pub mod reader {
         use std::io::Read;
         use std::net::TcpStream;
+        use std::sync::Arc;
         use crate::parser::body::{BodyReadError, Limits};
 
-        pub trait StreamReader {
-            fn get_chunk(&mut self) -> Result<Vec<u8>, BodyReadError>;
-            fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, BodyReadError>;
-        }
+        pub use crate::parser::reader::StreamReader;
 
         pub struct BodyReader {
-            stream: TcpStream,
+            stream: Arc<TcpStream>,
             content_length: usize,
             bytes_read: usize,
             limits: Limits,
         }
 
         impl BodyReader {
-            pub fn new(stream: TcpStream, content_length: usize, bytes_read: usize, limits: Limits) -> Self {
+            pub fn new<S: Into<Arc<TcpStream>>>(stream: S, content_length: usize, bytes_read: usize, limits: Limits) -> Self {
                 return Self {
-                    stream,
+                    stream: stream.into(),
                     content_length,
                     bytes_read,
                     limits,
@@ -48,6 +254,8 @@ pub mod body {
         }
 
         impl StreamReader for BodyReader {
+            type Error = BodyReadError;
+
             fn get_chunk(&mut self) -> Result<Vec<u8>, BodyReadError> {
                 if self.bytes_read >= self.content_length {
                     return Err(BodyReadError::MaxBodySizeExceed);
@@ -58,7 +266,7 @@ pub mod body {
                 }
 
                 let mut buffer = [0u8; 1024];
-                let read_result = self.stream.read(&mut buffer);
+                let read_result = self.stream.as_ref().read(&mut buffer);
                 if !read_result.is_ok() {
                     return Err(BodyReadError::Others(
                         "Unable to read stream. May be client disconnected."
@@ -81,7 +289,7 @@ pub mod body {
                 }
 
                 let mut buffer = vec![0u8; size];
-                let read_result = self.stream.read_exact(&mut buffer);
+                let read_result = self.stream.as_ref().read_exact(&mut buffer);
                 if !read_result.is_ok() {
                     return Err(BodyReadError::Others(
                         "Unable to read stream. May be client disconnected."
@@ -93,8 +301,8 @@ pub mod body {
         }
     }
 
-    pub fn parse<T: StreamReader>(partial_bytes: Vec<u8>, headers: &Headers, mut reader: T)
-                                  -> Result<NamedTempFile, BodyReadError> {
+    pub fn parse<T: StreamReader<Error: Into<BodyReadError>>>(partial_bytes: Vec<u8>, headers: &Headers, mut reader: T)
+                                                              -> Result<NamedTempFile, BodyReadError> {
         let mut body_buffer = Vec::from(partial_bytes);
         let mut body_read = body_buffer.len();
 
@@ -135,7 +343,7 @@ pub mod body {
 
             body_buffer.clear();
 
-            let read_result = reader.get_chunk();
+            let read_result = reader.get_chunk().map_err(Into::into);
             match read_result {
                 Ok(chunk) => {
                     body_read += chunk.len();
@@ -149,9 +357,33 @@ pub mod body {
     }
 }
 
+/// Options used while parsing url encoded text
+#[derive(Debug, Clone)]
+pub struct UrlEncodedOptions {
+    /// Characters which separate the key value pairs. Some APIs use `;` along with `&`.
+    /// Only `&` is used by default because value may contain `;` legitimately.
+    pub separators: Vec<char>,
+    /// Removes the trailing `[]` from the keys, so `tags[]=a&tags[]=b` is grouped under `tags`
+    pub strip_array_brackets: bool,
+}
+
+impl Default for UrlEncodedOptions {
+    fn default() -> Self {
+        return Self {
+            separators: vec!['&'],
+            strip_array_brackets: false,
+        };
+    }
+}
+
 pub fn parse_url_encoded(text: &str) -> HashMap<String, Vec<String>> {
+    return parse_url_encoded_with_options(text, &UrlEncodedOptions::default());
+}
+
+/// Parses url encoded text like `name=John&age=22` using the given options.
+pub fn parse_url_encoded_with_options(text: &str, options: &UrlEncodedOptions) -> HashMap<String, Vec<String>> {
     let mut params = HashMap::new();
-    let values = text.split("&");
+    let values = text.split(options.separators.as_slice());
 
     for value in values {
         let key_values: Vec<&str> = value.split("=").collect();
@@ -159,11 +391,15 @@ pub fn parse_url_encoded(text: &str) -> HashMap<String, Vec<String>> {
             let name = key_values.get(0).unwrap();
             let value = key_values.get(1).unwrap();
 
-            let name_formatted = url_decode(name);
+            let mut name_formatted = url_decode(name);
             let value_formatted = url_decode(value);
 
+            if options.strip_array_brackets && name_formatted.len() > 2 && name_formatted.ends_with("[]") {
+                name_formatted.truncate(name_formatted.len() - 2);
+            }
+
             if !params.contains_key(&name_formatted) {
-                params.insert(name.to_string(), Vec::new());
+                params.insert(name_formatted.clone(), Vec::new());
             }
 
             let values = params.get_mut(&name_formatted).unwrap();
@@ -173,6 +409,22 @@ pub fn parse_url_encoded(text: &str) -> HashMap<String, Vec<String>> {
     return params;
 }
 
+/// Builds url encoded text like `name=John&age=22` from the key value pairs. Inverse of
+/// [parse_url_encoded].
+pub fn build_query(params: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = params.iter().map(|(name, value)| {
+        format!("{}={}", url_encode(name), url_encode(value))
+    }).collect();
+
+    return pairs.join("&");
+}
+
+/// Percent encodes every character except the unreserved ones `A-Z a-z 0-9 - . _ ~`.
+/// Inverse of [url_decode].
+pub fn url_encode(value: &str) -> String {
+    return urlencoding::encode(value).into_owned();
+}
+
 pub fn url_decode(value: &str) -> String {
     return match urlencoding::decode(value) {
         Ok(decoded_value) => {
@@ -184,8 +436,228 @@ pub fn url_decode(value: &str) -> String {
     };
 }
 
+/// Decoding of the request body sent with `Content-Encoding` header
+pub mod content_encoding {
+    use std::error::Error;
+    use std::fmt::{Display, Formatter};
+
+    #[derive(Debug, PartialEq)]
+    pub enum DecodeError {
+        /// Occurs if the encoding is not `gzip` or `deflate` or `compression` feature is disabled
+        Unsupported(String),
+        /// Occurs if the body is not valid for the encoding
+        Invalid(&'static str),
+        /// Occurs if the decoded body is larger than the given limit
+        MaxSizeExceed,
+        /// Occurs if the decoded body is larger than `max_ratio` times of the encoded body
+        MaxRatioExceed,
+    }
+
+    impl Display for DecodeError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            return match self {
+                DecodeError::Unsupported(encoding) => write!(f, "Unsupported content encoding: {}", encoding),
+                DecodeError::Invalid(message) => write!(f, "{}", message),
+                DecodeError::MaxSizeExceed => write!(f, "Decoded body is larger than the limit"),
+                DecodeError::MaxRatioExceed => write!(f, "Decoded body is larger than the allowed ratio"),
+            };
+        }
+    }
+
+    impl Error for DecodeError {}
+
+    /// Limits applied while decoding. Decoding is stopped as soon as any of the limit is crossed,
+    /// so a small body can't expand to a huge size in the memory.
+    #[derive(Debug, Clone, Default)]
+    pub struct DecodeLimits {
+        /// Maximum size of the decoded body. Maximum body size of the request is used if not set.
+        pub max_size: Option<usize>,
+        /// Maximum ratio of the decoded size to the encoded size. Example: `100`
+        pub max_ratio: Option<usize>,
+    }
+
+    /// Returns true if the body with given `Content-Encoding` value needs decoding
+    pub fn is_encoded(content_encoding: &str) -> bool {
+        let content_encoding = content_encoding.trim();
+        return !content_encoding.is_empty() && !content_encoding.eq_ignore_ascii_case("identity");
+    }
+
+    /// Decodes the `gzip` or `deflate` encoded bytes. The decoded size must not exceed `max_size`
+    /// and the ratio limit if available.
+    #[cfg(feature = "compression")]
+    pub fn decode(content_encoding: &str, bytes: &[u8], max_size: usize, max_ratio: Option<usize>)
+                  -> Result<Vec<u8>, DecodeError> {
+        use std::io::Read;
+        use flate2::read::{GzDecoder, ZlibDecoder};
+
+        let mut decoder: Box<dyn Read + '_> = match content_encoding.trim().to_lowercase().as_str() {
+            "gzip" | "x-gzip" => Box::new(GzDecoder::new(bytes)),
+            "deflate" => Box::new(ZlibDecoder::new(bytes)),
+            other => {
+                return Err(DecodeError::Unsupported(other.to_string()));
+            }
+        };
+
+        let max_ratio_size = match max_ratio {
+            Some(max_ratio) => bytes.len().saturating_mul(max_ratio),
+            None => usize::MAX,
+        };
+
+        let mut decoded = Vec::new();
+        let mut buffer = [0u8; 8192];
+
+        loop {
+            let read_size = match decoder.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read_size) => read_size,
+                Err(_) => {
+                    return Err(DecodeError::Invalid("Failed to decode body"));
+                }
+            };
+
+            decoded.extend(&buffer[..read_size]);
+
+            if decoded.len() > max_size {
+                return Err(DecodeError::MaxSizeExceed);
+            }
+
+            if decoded.len() > max_ratio_size {
+                return Err(DecodeError::MaxRatioExceed);
+            }
+        }
+
+        return Ok(decoded);
+    }
+
+    #[cfg(not(feature = "compression"))]
+    pub fn decode(content_encoding: &str, _bytes: &[u8], _max_size: usize, _max_ratio: Option<usize>)
+                  -> Result<Vec<u8>, DecodeError> {
+        return Err(DecodeError::Unsupported(content_encoding.trim().to_string()));
+    }
+}
+
+pub mod transfer_encoding {
+    use std::io::Read;
+    use crate::parser::body::BodyReadError;
+
+    /// Maximum length of the chunk size line including the chunk extensions
+    const MAX_LINE_SIZE: usize = 4 * 1024; // 4 KiB
+
+    /// Returns the lowercase transfer codings of `Transfer-Encoding` values in the applied order
+    pub fn codings(values: &[String]) -> Vec<String> {
+        let mut codings = Vec::new();
+
+        for value in values {
+            for coding in value.split(',') {
+                let coding = coding.trim();
+                if !coding.is_empty() {
+                    codings.push(coding.to_lowercase());
+                }
+            }
+        }
+
+        return codings;
+    }
+
+    /// Request body length is known only if `chunked` is the last coding. It must not be applied
+    /// more than once.
+    pub fn validate_codings(codings: &[String]) -> Result<(), BodyReadError> {
+        if codings.last().map(|coding| coding.as_str()) != Some("chunked") {
+            return Err(BodyReadError::InvalidTransferEncoding("Chunked must be the last transfer coding"));
+        }
+
+        if codings.iter().filter(|coding| *coding == "chunked").count() > 1 {
+            return Err(BodyReadError::InvalidTransferEncoding("Chunked is applied more than once"));
+        }
+
+        return Ok(());
+    }
+
+    /// Decodes the chunked body. Bytes already read from the stream are passed in `buffer`.
+    /// Returns the decoded body and the bytes read after the end of the chunked body.
+    pub fn decode_chunked<R: Read + ?Sized>(mut buffer: Vec<u8>, reader: &mut R, max_size: usize)
+                                            -> Result<(Vec<u8>, Vec<u8>), BodyReadError> {
+        let mut body = Vec::new();
+
+        loop {
+            let line = read_line(&mut buffer, reader)?;
+            let size_text = line.split(';').next().unwrap().trim();
+
+            if size_text.is_empty() || !size_text.chars().all(|character| character.is_ascii_hexdigit()) {
+                return Err(BodyReadError::InvalidTransferEncoding("Invalid chunk size"));
+            }
+
+            let size = match usize::from_str_radix(size_text, 16) {
+                Ok(size) => size,
+                Err(_) => {
+                    return Err(BodyReadError::InvalidTransferEncoding("Invalid chunk size"));
+                }
+            };
+
+            if size == 0 {
+                // Trailer fields are ignored
+                while !read_line(&mut buffer, reader)?.is_empty() {}
+                return Ok((body, buffer));
+            }
+
+            if body.len().saturating_add(size) > max_size {
+                return Err(BodyReadError::MaxBodySizeExceed);
+            }
+
+            while buffer.len() < size + 2 {
+                fill_buffer(&mut buffer, reader)?;
+            }
+
+            if &buffer[size..size + 2] != b"\r\n" {
+                return Err(BodyReadError::InvalidTransferEncoding("Chunk does not end with CRLF"));
+            }
+
+            body.extend(&buffer[..size]);
+            buffer.drain(..size + 2);
+        }
+    }
+
+    /// Removes the line including CRLF from the start of the buffer and returns it
+    fn read_line<R: Read + ?Sized>(buffer: &mut Vec<u8>, reader: &mut R) -> Result<String, BodyReadError> {
+        loop {
+            if let Some(index) = buffer.windows(2).position(|window| window == b"\r\n") {
+                let line = String::from_utf8_lossy(&buffer[..index]).to_string();
+                buffer.drain(..index + 2);
+                return Ok(line);
+            }
+
+            if buffer.len() > MAX_LINE_SIZE {
+                return Err(BodyReadError::InvalidTransferEncoding("Chunk line is too long"));
+            }
+
+            fill_buffer(buffer, reader)?;
+        }
+    }
+
+    fn fill_buffer<R: Read + ?Sized>(buffer: &mut Vec<u8>, reader: &mut R) -> Result<(), BodyReadError> {
+        let mut chunk = [0u8; 8192];
+
+        match reader.read(&mut chunk) {
+            Ok(0) => {
+                return Err(BodyReadError::Others("Body ended before the last chunk"));
+            }
+
+            Ok(read_size) => {
+                buffer.extend(&chunk[..read_size]);
+                return Ok(());
+            }
+
+            Err(_) => {
+                return Err(BodyReadError::Others("Unable to read stream. May be client disconnected."));
+            }
+        }
+    }
+}
+
 pub mod url_encoded {
     use std::collections::HashMap;
+    use std::error::Error;
+    use std::fmt::{Display, Formatter};
     use crate::headers;
     use crate::headers::{Headers};
     use crate::parser::parse_url_encoded;
@@ -203,23 +675,38 @@ pub mod url_encoded {
         MaxBodySizeExceed(&'static str),
         /// Occurs, if parser requires more data to parse fully, but there is no more data left
         BodyReadEnd,
+        /// Occurs, if the body read is cancelled
+        Cancelled,
         /// Occurs, if error not fulfilled by above conditions
         Others(&'static str),
     }
 
+    impl Display for UrlEncodedFormDataError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            return match self {
+                UrlEncodedFormDataError::InvalidFormat(message)
+                | UrlEncodedFormDataError::ContentLengthMissing(message)
+                | UrlEncodedFormDataError::ParsingError(message)
+                | UrlEncodedFormDataError::MaxBodySizeExceed(message)
+                | UrlEncodedFormDataError::Others(message) => write!(f, "{}", message),
+                UrlEncodedFormDataError::BodyReadEnd => write!(f, "Body ended before it was fully parsed"),
+                UrlEncodedFormDataError::Cancelled => write!(f, "Body read is cancelled"),
+            };
+        }
+    }
+
+    impl Error for UrlEncodedFormDataError {}
+
     pub mod reader {
         use std::io::Read;
         use std::net::TcpStream;
+        use std::sync::Arc;
         use crate::parser::url_encoded::UrlEncodedFormDataError;
 
-        /// The reusable trait for fetching "x-www-form-urlencoded" form data
-        pub trait StreamReader {
-            fn get_chunk(&mut self) -> Result<Vec<u8>, UrlEncodedFormDataError>;
-            fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, UrlEncodedFormDataError>;
-        }
+        pub use crate::parser::reader::StreamReader;
 
         pub struct UrlEncodedReader {
-            pub stream: TcpStream,
+            pub stream: Arc<TcpStream>,
             pub content_length: usize,
             // Size of bytes that has been already read
             pub bytes_read: usize,
@@ -227,7 +714,7 @@ pub mod url_encoded {
         }
 
         impl UrlEncodedReader {
-            pub fn new(stream: TcpStream, content_length: usize, bytes_read: usize) -> Self {
+            pub fn new<S: Into<Arc<TcpStream>>>(stream: S, content_length: usize, bytes_read: usize) -> Self {
                 let body_ended;
 
                 if bytes_read == content_length {
@@ -237,7 +724,7 @@ pub mod url_encoded {
                 };
 
                 return Self {
-                    stream,
+                    stream: stream.into(),
                     content_length,
                     bytes_read,
                     body_ended,
@@ -254,13 +741,15 @@ pub mod url_encoded {
         }
 
         impl StreamReader for UrlEncodedReader {
+            type Error = UrlEncodedFormDataError;
+
             fn get_chunk(&mut self) -> Result<Vec<u8>, UrlEncodedFormDataError> {
                 if self.body_ended {
                     return Err(UrlEncodedFormDataError::BodyReadEnd);
                 }
 
                 let mut buffer = [0u8; 1024];
-                let read_result = self.stream.read(&mut buffer);
+                let read_result = self.stream.as_ref().read(&mut buffer);
 
                 if !read_result.is_ok() {
                     return Err(UrlEncodedFormDataError::Others(
@@ -285,7 +774,7 @@ pub mod url_encoded {
                 }
 
                 let mut buffer = vec![0u8; size];
-                let result = self.stream.read_exact(&mut buffer);
+                let result = self.stream.as_ref().read_exact(&mut buffer);
                 if !result.is_ok() {
                     return Err(UrlEncodedFormDataError::Others(
                         "Unable to read stream. May be client disconnected."
@@ -303,8 +792,9 @@ pub mod url_encoded {
 
     pub type FormFields = HashMap<String, Vec<String>>;
 
-    pub fn parse<T: StreamReader>(partial_bytes: Vec<u8>, headers: &Headers, reader: &mut T,
-                                  limits: Limits) -> Result<FormFields, UrlEncodedFormDataError> {
+    pub fn parse<T: StreamReader<Error: Into<UrlEncodedFormDataError>> + ?Sized>(
+        partial_bytes: Vec<u8>, headers: &Headers, reader: &mut T, limits: Limits,
+    ) -> Result<FormFields, UrlEncodedFormDataError> {
         let mut body_buffer = Vec::from(partial_bytes);
         let content_length = headers::content_length(headers);
 
@@ -321,11 +811,10 @@ pub mod url_encoded {
         }
 
         let content_length = content_length.unwrap();
-        let bytes_read = body_buffer.len();
 
         // Load all the request body to memory
-        while content_length > bytes_read {
-            let request_chunk = reader.get_chunk();
+        while content_length > body_buffer.len() {
+            let request_chunk = reader.get_chunk().map_err(Into::into);
 
             match request_chunk {
                 Ok(chunk) => {
@@ -338,6 +827,8 @@ pub mod url_encoded {
             }
         };
 
+        // Bytes after Content-Length are not part of this body
+        body_buffer.truncate(content_length);
         let value = String::from_utf8_lossy(&body_buffer).to_string();
         let form_values = parse_url_encoded(value.as_str());
         return Ok(form_values);
@@ -346,8 +837,9 @@ pub mod url_encoded {
 
 pub mod multipart {
     use std::collections::HashMap;
+    use std::error::Error;
+    use std::fmt::{Display, Formatter};
     use std::io::{Seek, SeekFrom, Write};
-    use regex::Regex;
     use tempfile::NamedTempFile;
     use crate::headers;
     use crate::headers::Headers;
@@ -366,37 +858,88 @@ pub mod multipart {
         MaxFieldSizeExceed(String, &'static str),
         /// Occurs, if parser requires more data to parse fully, but there is no more data left
         BodyReadEnd,
+        /// Occurs, if the body ended before the closing boundary. Usually the Content-Length is
+        /// smaller than the actual body.
+        IncompleteBody(&'static str),
+        /// Occurs, if the client disconnected before the body was fully read. Temporary files of
+        /// the partially received parts are removed.
+        ClientDisconnected,
+        /// Occurs, if the body read is cancelled. Temporary files of the partially received parts
+        /// are removed.
+        Cancelled,
         /// Occurs, if error not fulfilled by above conditions
         Others(&'static str),
     }
 
-
-    /// The reusable trait for fetching multipart bytes
-    pub trait StreamReader {
-        fn get_chunk(&mut self) -> Result<Vec<u8>, MultipartFormDataError>;
-        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, MultipartFormDataError>;
+    impl Display for MultipartFormDataError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            return match self {
+                MultipartFormDataError::InvalidMultiPart(message)
+                | MultipartFormDataError::ParsingError(message)
+                | MultipartFormDataError::HeaderSizeExceed(message)
+                | MultipartFormDataError::MaxBodySizeExceed(message)
+                | MultipartFormDataError::IncompleteBody(message)
+                | MultipartFormDataError::Others(message) => write!(f, "{}", message),
+                MultipartFormDataError::MaxFieldSizeExceed(name, message) => write!(f, "{}: {}", name, message),
+                MultipartFormDataError::BodyReadEnd => write!(f, "Body ended before it was fully parsed"),
+                MultipartFormDataError::ClientDisconnected => write!(f, "Client disconnected before sending the full body"),
+                MultipartFormDataError::Cancelled => write!(f, "Body read is cancelled"),
+            };
+        }
     }
 
+    impl Error for MultipartFormDataError {}
+
+
+    pub use crate::parser::reader::StreamReader;
+
     /// Extracts boundary from Content-Type header.
     pub fn extract_boundary(content_type: &String) -> Option<String> {
-        let value: Vec<&str> = content_type.split(";").collect();
-
-        if value.len() >= 2 {
-            let content_type_text = value.get(1).unwrap().trim();
-            let boundary = content_type_text.strip_prefix("boundary=").unwrap();
-            return Some(boundary.to_string());
+        for parameter in content_type.split(";").skip(1) {
+            if let Some((name, value)) = parameter.split_once("=") {
+                if name.trim().eq_ignore_ascii_case("boundary") {
+                    let boundary = value.trim().trim_matches('"');
+                    return Some(boundary.to_string());
+                }
+            }
         }
 
         return None;
     }
 
+    /// Maximum length of the boundary allowed by RFC 2046
+    pub const MAX_BOUNDARY_LENGTH: usize = 70;
+
+    /// Validates the boundary according to RFC 2046. Boundary must be 1 to 70 characters long
+    /// and only contain the allowed characters. It must not end with space.
+    pub fn validate_boundary(boundary: &str) -> Result<(), MultipartFormDataError> {
+        if boundary.is_empty() {
+            return Err(MultipartFormDataError::InvalidMultiPart("Multipart boundary is empty."));
+        }
+
+        if boundary.len() > MAX_BOUNDARY_LENGTH {
+            return Err(MultipartFormDataError::InvalidMultiPart("Multipart boundary is longer than 70 characters."));
+        }
+
+        let is_allowed = |character: char| {
+            return character.is_ascii_alphanumeric() || "'()+_,-./:=? ".contains(character);
+        };
+
+        if !boundary.chars().all(is_allowed) || boundary.ends_with(' ') {
+            return Err(MultipartFormDataError::InvalidMultiPart("Multipart boundary contains invalid characters."));
+        }
+
+        return Ok(());
+    }
+
     pub mod reader {
         use std::io::Read;
         use std::net::TcpStream;
+        use std::sync::Arc;
         use crate::parser::multipart::{MultipartFormDataError, StreamReader};
 
         pub struct FormDataReader {
-            pub stream: TcpStream,
+            pub stream: Arc<TcpStream>,
             pub boundary_end_bytes: Vec<u8>,
             pub content_length: Option<usize>,
             // Size of bytes that has been already read
@@ -407,7 +950,7 @@ pub mod multipart {
         }
 
         impl FormDataReader {
-            pub fn new(stream: TcpStream, boundary: String, content_length: Option<usize>, body_read: usize) -> Self {
+            pub fn new<S: Into<Arc<TcpStream>>>(stream: S, boundary: String, content_length: Option<usize>, body_read: usize) -> Self {
                 let boundary_end = format!("--{}\r\n", boundary);
                 let boundary_end_bytes = boundary_end.as_bytes().to_vec();
                 let body_buffer = Vec::with_capacity(boundary_end_bytes.len());
@@ -422,7 +965,7 @@ pub mod multipart {
                 }
 
                 return Self {
-                    stream,
+                    stream: stream.into(),
                     boundary_end_bytes,
                     content_length,
                     bytes_read: body_read,
@@ -431,6 +974,16 @@ pub mod multipart {
                 };
             }
 
+            /// Returns the maximum number of bytes that can be read without going beyond Content-Length
+            fn read_size_limit(&self, size: usize) -> usize {
+                if let Some(content_length) = self.content_length {
+                    let bytes_left = content_length.saturating_sub(self.bytes_read);
+                    return size.min(bytes_left);
+                }
+
+                return size;
+            }
+
             /// Performs checks and updates status
             fn update_read_status(&mut self, new_chunk: &[u8]) {
                 self.bytes_read += new_chunk.len();
@@ -468,6 +1021,8 @@ pub mod multipart {
         }
 
         impl StreamReader for FormDataReader {
+            type Error = MultipartFormDataError;
+
             fn get_chunk(&mut self) -> Result<Vec<u8>, MultipartFormDataError> {
                 if self.body_ended {
                     return Err(MultipartFormDataError::BodyReadEnd);
@@ -475,16 +1030,17 @@ pub mod multipart {
 
                 const BUFFER_SIZE: usize = 8 * 1024; // 8 KiB
                 let mut buffer = [0u8; BUFFER_SIZE];
-                let result = self.stream.read(&mut buffer);
 
-                if !result.is_ok() {
-                    return Err(MultipartFormDataError::Others("Unable to read stream. May be client disconnected."));
-                }
+                // Never read beyond Content-Length. Remaining bytes belong to the next request.
+                let read_limit = self.read_size_limit(BUFFER_SIZE);
+                let result = self.stream.as_ref().read(&mut buffer[..read_limit]);
 
-                let read_size = result.unwrap();
-                if read_size == 0 {
-                    return Err(MultipartFormDataError::Others("Bytes read size is 0. Probably client disconnected."));
-                }
+                let read_size = match result {
+                    Ok(read_size) if read_size > 0 => read_size,
+                    _ => {
+                        return Err(MultipartFormDataError::ClientDisconnected);
+                    }
+                };
 
                 let chunk_slice = &buffer[0..read_size];
                 self.update_read_status(&chunk_slice);
@@ -498,10 +1054,15 @@ pub mod multipart {
                     return Err(MultipartFormDataError::BodyReadEnd);
                 }
 
+                if self.read_size_limit(size) < size {
+                    // Requested more bytes than Content-Length allows
+                    return Err(MultipartFormDataError::BodyReadEnd);
+                }
+
                 let mut buffer: Vec<u8> = vec![0u8; size];
-                let result = self.stream.read_exact(&mut buffer);
+                let result = self.stream.as_ref().read_exact(&mut buffer);
                 if !result.is_ok() {
-                    return Err(MultipartFormDataError::Others("Unable to read stream. May be client disconnected."));
+                    return Err(MultipartFormDataError::ClientDisconnected);
                 }
 
                 self.update_read_status(&buffer);
@@ -542,6 +1103,57 @@ pub mod multipart {
                 form_part_limits: HashMap::new(),
             };
         }
+
+        /// Returns the builder which starts without any limit.
+        ///
+        /// ```rust
+        /// use rusty_web::parser::multipart::{FormPartLimit, Limits};
+        ///
+        /// let limits = Limits::builder()
+        ///     .max_body_size(10 * 1024 * 1024)
+        ///     .max_header_size(1024)
+        ///     .part_limit("avatar", FormPartLimit { max_size: Some(1024 * 1024), content_type: None })
+        ///     .build();
+        /// ```
+        pub fn builder() -> LimitsBuilder {
+            return LimitsBuilder {
+                limits: Self::none(),
+            };
+        }
+    }
+
+    /// Builds `Limits` using chained setters. See `Limits::builder`.
+    #[derive(Debug)]
+    pub struct LimitsBuilder {
+        limits: Limits,
+    }
+
+    impl LimitsBuilder {
+        pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+            self.limits.max_body_size = Some(max_body_size);
+            return self;
+        }
+
+        pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+            self.limits.max_header_size = Some(max_header_size);
+            return self;
+        }
+
+        /// Maximum size of the field values which don't have their own part limit
+        pub fn max_value_size(mut self, max_value_size: usize) -> Self {
+            self.limits.max_value_size = Some(max_value_size);
+            return self;
+        }
+
+        /// Sets the limit of the form part with the given name
+        pub fn part_limit(mut self, name: &str, limit: FormPartLimit) -> Self {
+            self.limits.form_part_limits.insert(name.to_string(), limit);
+            return self;
+        }
+
+        pub fn build(self) -> Limits {
+            return self.limits;
+        }
     }
 
     #[derive(Debug)]
@@ -584,7 +1196,7 @@ pub mod multipart {
     /// male
     /// ----------------------------648887867674240986891965--
     /// ```
-    pub fn parse<T: StreamReader>(partial_bytes: Vec<u8>, headers: &Headers, reader: T, limits: Limits)
+    pub fn parse<T: StreamReader<Error: Into<MultipartFormDataError>>>(partial_bytes: Vec<u8>, headers: &Headers, mut reader: T, limits: Limits)
                                   -> Result<Vec<FormPart>, MultipartFormDataError> {
         let content_type_bytes = headers.get("Content-Type");
 
@@ -602,6 +1214,8 @@ pub mod multipart {
             return Err(MultipartFormDataError::InvalidMultiPart("Unable to extract multipart boundary."));
         }
 
+        validate_boundary(&multipart_boundary)?;
+
         // Check if the client body is larger than the limit
         if let Some(max_body_size) = limits.max_body_size {
             if let Some(content_length) = headers::content_length(&headers) {
@@ -611,42 +1225,36 @@ pub mod multipart {
             }
         }
 
-        let body_buffer = Vec::from(partial_bytes);
-        return parse_body_parts(reader, body_buffer, &multipart_boundary, limits);
-    }
-
-    pub fn parse_body_parts<T: StreamReader>(mut reader: T, mut body_buffer: Vec<u8>, boundary: &String,
-                                             limits: Limits) -> Result<Vec<FormPart>, MultipartFormDataError> {
-        let mut form_parts = Vec::new();
+        let mut body_buffer = Vec::from(partial_bytes);
 
-        // Remove starting boundary first. It will make parsing easy by matching \r\n--{boundary}
+        // Bytes after Content-Length are not part of this body
+        if let Some(content_length) = headers::content_length(headers) {
+            body_buffer.truncate(content_length);
+        }
 
-        let start_boundary = format!("--{}\r\n", boundary);
-        let start_boundary_bytes = start_boundary.as_bytes();
+        let result = parse_body_parts(&mut reader, body_buffer, &multipart_boundary, limits);
 
-        // All the data is not be received. If not received try to read the required number bytes to make the boundary string.
-        if body_buffer.len() <= start_boundary_bytes.len() {
-            // Instead of reading bytes of some length, we will read exactly bytes required to prevent from reading again.
-            let bytes_required = start_boundary_bytes.len() - body_buffer.len();
-            let chunk_request_result = reader.get_exact(bytes_required);
+        // Epilogue after the closing boundary is discarded. Without Content-Length, the end of
+        // the epilogue is unknown, so it is left unread.
+        if result.is_ok() && headers::content_length(headers).is_some() {
+            while reader.get_chunk().is_ok() {}
+        }
 
-            match chunk_request_result {
-                Ok(chunk) => {
-                    body_buffer.extend(chunk);
-                }
+        if let Err(MultipartFormDataError::BodyReadEnd) = result {
+            return Err(MultipartFormDataError::IncompleteBody(
+                "Body ended before the closing boundary. Content-Length may not match the body."
+            ));
+        }
 
-                Err(error) => {
-                    return Err(error);
-                }
-            }
-        };
+        return result;
+    }
 
-        if !body_buffer_starts_with_boundary(&body_buffer, start_boundary_bytes) {
-            return Err(MultipartFormDataError::InvalidMultiPart("Body does not start with boundary"));
-        }
+    pub fn parse_body_parts<T: StreamReader<Error: Into<MultipartFormDataError>>>(mut reader: T, mut body_buffer: Vec<u8>, boundary: &String,
+                                             limits: Limits) -> Result<Vec<FormPart>, MultipartFormDataError> {
+        let mut form_parts = Vec::new();
 
-        // Remove boundary header start
-        body_buffer = Vec::from(&body_buffer[start_boundary_bytes.len()..]);
+        // Remove starting boundary first. It will make parsing easy by matching \r\n--{boundary}
+        skip_preamble(&mut reader, &mut body_buffer, boundary)?;
 
         // Now, we can start looping the form part contents.
         loop {
@@ -705,10 +1313,77 @@ pub mod multipart {
         }
     }
 
+    /// Removes the bytes up to and including the first boundary line from the body buffer. Any
+    /// preamble before the first boundary is discarded as allowed by RFC 2046. This also
+    /// tolerates the stray CRLFs which some clients send before the first boundary.
+    ///
+    /// ```markdown
+    /// This is the preamble. It is ignored.
+    /// --boundary
+    /// Content-Disposition: form-data; name="name"
+    /// ```
+    pub fn skip_preamble<T: StreamReader<Error: Into<MultipartFormDataError>> + ?Sized>(reader: &mut T, body_buffer: &mut Vec<u8>, boundary: &String)
+                                                                                       -> Result<(), MultipartFormDataError> {
+        let start_boundary = format!("--{}\r\n", boundary);
+        let start_boundary_bytes = start_boundary.as_bytes();
+        // Boundary after the preamble is always preceded by CRLF
+        let delimiter = format!("\r\n--{}\r\n", boundary);
+        let delimiter_bytes = delimiter.as_bytes();
+
+        let mut preamble_discarded = false;
+
+        loop {
+            if !preamble_discarded && body_buffer.len() >= start_boundary_bytes.len()
+                && body_buffer_starts_with_boundary(body_buffer, start_boundary_bytes) {
+                body_buffer.drain(..start_boundary_bytes.len());
+                return Ok(());
+            }
+
+            let delimiter_position = body_buffer.windows(delimiter_bytes.len())
+                .position(|window| window == delimiter_bytes);
+
+            if let Some(position) = delimiter_position {
+                body_buffer.drain(..position + delimiter_bytes.len());
+                return Ok(());
+            }
+
+            // Only the last bytes are kept as they may be the start of the delimiter
+            if body_buffer.len() >= delimiter_bytes.len() {
+                body_buffer.drain(..body_buffer.len() - delimiter_bytes.len() + 1);
+                preamble_discarded = true;
+            }
+
+            match reader.get_chunk().map_err(Into::into) {
+                Ok(chunk) => {
+                    body_buffer.extend(chunk);
+                }
+
+                Err(error) => {
+                    return Err(error);
+                }
+            }
+        }
+    }
+
     pub fn body_buffer_starts_with_boundary(body_buffer: &Vec<u8>, start_boundary_bytes: &[u8]) -> bool {
         // Check if the body buffer starts with start boundary or not. If not we will discard and don't process further.
-        let extracted_boundary_slice = &body_buffer[0..start_boundary_bytes.len()];
-        return extracted_boundary_slice == start_boundary_bytes;
+        return body_buffer.starts_with(start_boundary_bytes);
+    }
+
+    /// Reads until the body buffer contains at least `size` bytes. Readers may return fewer bytes
+    /// than requested, so it is read again. Returns `BodyReadEnd` if the reader has no more bytes.
+    fn fill_body_buffer<T: StreamReader<Error: Into<MultipartFormDataError>> + ?Sized>(reader: &mut T, body_buffer: &mut Vec<u8>, size: usize)
+                                                                                      -> Result<(), MultipartFormDataError> {
+        while body_buffer.len() < size {
+            let chunk = reader.get_exact(size - body_buffer.len()).map_err(Into::into)?;
+            if chunk.is_empty() {
+                return Err(MultipartFormDataError::BodyReadEnd);
+            }
+
+            body_buffer.extend(chunk);
+        }
+
+        return Ok(());
     }
 
     /// Parses the form part header and removes the header from body buffer including \r\n\r\n bytes.
@@ -725,7 +1400,7 @@ pub mod multipart {
     ///
     /// ... continues
     /// ```
-    pub fn extract_form_part_header<T: StreamReader>(reader: &mut T, body_buffer: &mut Vec<u8>, limits: &Limits)
+    pub fn extract_form_part_header<T: StreamReader<Error: Into<MultipartFormDataError>> + ?Sized>(reader: &mut T, body_buffer: &mut Vec<u8>, limits: &Limits)
                                                      -> Result<Vec<u8>, MultipartFormDataError> {
         // There can be one CRLF line break as well as two. Need to handle both cases.
         let header_end_bytes = b"\r\n\r\n";
@@ -750,23 +1425,19 @@ pub mod multipart {
                 *body_buffer = Vec::from(&body_buffer[found_index + header_end_bytes.len()..]);
                 return Ok(form_part_header_buffer);
             } else {
-                // Header is not found yet. However, we copy the unmatched buffer too except last 4 bytes;
-                // Last 4 bytes not copied to header buffer because it's half part may be available in the buffer next time
-                // after new read. So we can't check if it ends or not.
-                // If there is no enough data to copy to header buffer we ignore and fill more data to body buffer.
-                let to_copy_to_header_buffer = body_buffer.len() as i32 - header_end_bytes.len() as i32;
-                if to_copy_to_header_buffer > 0 {
-                    // Append new data to header buffer
-                    form_part_header_buffer.extend(header_end_bytes);
-                    // Also remove copied data from body buffer
-                    *body_buffer = Vec::from(&body_buffer[to_copy_to_header_buffer as usize..]);
+                // Header is not found yet. The unmatched bytes are moved to the header buffer except the
+                // last 3 bytes, as they may be the start of \r\n\r\n which is completed by the next read.
+                let bytes_to_keep = header_end_bytes.len() - 1;
+                if body_buffer.len() > bytes_to_keep {
+                    let to_copy_size = body_buffer.len() - bytes_to_keep;
+                    form_part_header_buffer.extend(body_buffer.drain(..to_copy_size));
                 }
 
                 // If MAX_HEADER_SIZE exceeds, return error.
                 if max_header_size.is_some() && (form_part_header_buffer.len() >= max_header_size.unwrap()) {
                     return Err(MultipartFormDataError::HeaderSizeExceed("Header size exceed max specified size"));
                 } else {
-                    let request_new_chunk = reader.get_chunk();
+                    let request_new_chunk = reader.get_chunk().map_err(Into::into);
 
                     match request_new_chunk {
                         Ok(new_chunk) => {
@@ -827,33 +1498,55 @@ pub mod multipart {
     /// form-data; name="file"; filename="hello.txt"
     /// ```
     pub fn parse_content_disposition_value(value: &str, form_part: &mut FormPart) {
-        let value = value.trim();
+        let (disposition_type, parameters) = value.split_once(';').unwrap_or((value, ""));
 
-        if !value.starts_with("form-data;") {
+        if !disposition_type.trim().eq_ignore_ascii_case("form-data") {
             // Not a valid Content-Deposition value for form part header
             return;
         }
 
-        let remaining = value.strip_prefix("form-data;").unwrap().trim();
-        let pattern = Regex::new(r#"(?<attribute>\w+)="(?<value>[^"]*)""#).unwrap();
-
-        for captured in pattern.captures_iter(remaining) {
-            let attribute = &captured["attribute"];
-            let value = &captured["value"];
+        // Extended filename contains non ASCII characters, so it is preferred if available
+        let mut extended_filename = None;
 
+        for (attribute, value) in headers::parse_parameters(parameters) {
             if attribute == "name" {
-                form_part.name = Some(value.to_string());
+                form_part.name = Some(value);
             } else if attribute == "filename" {
-                form_part.filename = Some(value.to_string());
+                form_part.filename = Some(unescape_filename(&value));
+            } else if attribute == "filename*" {
+                extended_filename = headers::decode_ext_value(&value);
             }
         }
+
+        if extended_filename.is_some() {
+            form_part.filename = extended_filename;
+        }
+    }
+
+    /// Browsers percent encode quotes and line breaks of the filename. Other percent encoded
+    /// characters are kept as it is, as they may be part of the actual filename.
+    fn unescape_filename(filename: &str) -> String {
+        return filename.replace("%22", "\"")
+            .replace("%0D", "\r")
+            .replace("%0A", "\n");
     }
 
     pub fn parse_content_type(value: &str, form_part: &mut FormPart) {
         form_part.content_type = Some(value.to_string());
     }
 
-    pub fn extract_form_part_body<T: StreamReader>(reader: &mut T, body_buffer: &mut Vec<u8>, boundary: &String,
+    /// Returns true if the bytes written is larger than the max size of the form part limit
+    fn exceeds_part_limit(form_part_limit: Option<&FormPartLimit>, bytes_written: usize) -> bool {
+        if let Some(form_part_limit) = form_part_limit {
+            if let Some(max_size) = form_part_limit.max_size {
+                return bytes_written > max_size;
+            }
+        }
+
+        return false;
+    }
+
+    pub fn extract_form_part_body<T: StreamReader<Error: Into<MultipartFormDataError>> + ?Sized>(reader: &mut T, body_buffer: &mut Vec<u8>, boundary: &String,
                                                    form_part: &mut FormPart, limits: &Limits) ->
                                                    Result<FormPartResult, MultipartFormDataError> {
         let field_name = &form_part.name;
@@ -888,18 +1581,9 @@ pub mod multipart {
         return extract_form_value(reader, body_buffer, boundary, form_part, form_part_limit);
     }
 
-    /// It writes the file to temporary file.
-    /// Example to copy file
-    /// ```markdown
-    /// // Example to copy temp file
-    ///
-    /// let filename = &form_part.filename.unwrap();
-    /// let owned = filename.to_owned();
-    /// let path = temp_file.path();
-    ///
-    /// fs::copy(path, owned).expect("Error copying");
-    /// ```
-    pub fn extract_form_file_body<T: StreamReader>(reader: &mut T, body_buffer: &mut Vec<u8>, boundary: &String,
+    /// It writes the file to temporary file. Use `FormFile::save_to` or `FormFile::persist` of
+    /// the request files to keep the uploaded file.
+    pub fn extract_form_file_body<T: StreamReader<Error: Into<MultipartFormDataError>> + ?Sized>(reader: &mut T, body_buffer: &mut Vec<u8>, boundary: &String,
                                                    form_part: &mut FormPart, form_part_limit: Option<&FormPartLimit>)
                                                    -> Result<FormPartResult, MultipartFormDataError> {
         // Create new tmp directory
@@ -948,13 +1632,14 @@ pub mod multipart {
                     if !write_result.is_ok() {
                         return Err(MultipartFormDataError::Others("Error writing to temporary file"));
                     }
-
-                    // Remove copied data from body buffer including boundary by creating new array.
-                    *body_buffer = Vec::from(&body_buffer[body_end_index + file_end_matching_bytes.len()..]);
                 }
 
+                // Remove copied data from body buffer including boundary. Zero byte file still has
+                // the boundary to remove.
+                *body_buffer = Vec::from(&body_buffer[body_end_index + file_end_matching_bytes.len()..]);
+
                 // Check if the file size is more than the limit set.
-                if form_part_limit.is_some() && (bytes_written > form_part_limit.unwrap().max_size.unwrap()) {
+                if exceeds_part_limit(form_part_limit, bytes_written) {
                     return Err(MultipartFormDataError::MaxFieldSizeExceed(
                         form_part.name.clone().unwrap().to_string(),
                         "The file is bigger than the maximum allowed size")
@@ -971,24 +1656,10 @@ pub mod multipart {
                 // Read exact 4 bytes if there is nothing in the body buffer else request required number of bytes.
                 // 4 bytes should be there before completing request body.
 
-                if body_buffer.len() < 4 {
-                    // Amount of bytes to read
-                    let bytes_to_read = 4 - body_buffer.len();
-
-                    let request_new_chunk = reader.get_exact(bytes_to_read);
-                    match request_new_chunk {
-                        Ok(chunk) => {
-                            body_buffer.extend(chunk);
-                        }
-                        Err(error) => {
-                            return Err(error);
-                        }
-                    }
-                }
+                fill_body_buffer(reader, body_buffer, 4)?;
 
                 // Compare --\r\n
-                let body_end_compare = &body_buffer[0..4];
-                if body_end_compare == end_body_bytes {
+                if body_buffer.starts_with(end_body_bytes) {
                     // All form part has been parsed
                     body_buffer.clear();
                     if !temp_file.seek(SeekFrom::Start(0)).is_ok() {
@@ -1000,8 +1671,7 @@ pub mod multipart {
                 }
 
                 // Compare \r\n
-                let form_part_next_compare = &body_buffer[0..2];
-                if form_part_next_compare == next_part_bytes {
+                if body_buffer.starts_with(next_part_bytes) {
                     // Remove \r\n bytes from the body buffer
                     *body_buffer = Vec::from(&body_buffer[2..]);
 
@@ -1042,13 +1712,13 @@ pub mod multipart {
                     bytes_written += to_copy_size as usize;
                 }
 
-                if form_part_limit.is_some() && (bytes_written > form_part_limit.unwrap().max_size.unwrap()) {
+                if exceeds_part_limit(form_part_limit, bytes_written) {
                     return Err(MultipartFormDataError::MaxFieldSizeExceed(
                         form_part.name.clone().unwrap().to_string(),
                         "The file is bigger than the maximum allowed size"));
                 }
 
-                let request_new_chunk = reader.get_chunk();
+                let request_new_chunk = reader.get_chunk().map_err(Into::into);
 
                 match request_new_chunk {
                     Ok(new_chunk) => {
@@ -1063,7 +1733,7 @@ pub mod multipart {
         };
     }
 
-    pub fn extract_form_value<T: StreamReader>(reader: &mut T, body_buffer: &mut Vec<u8>, boundary: &String,
+    pub fn extract_form_value<T: StreamReader<Error: Into<MultipartFormDataError>> + ?Sized>(reader: &mut T, body_buffer: &mut Vec<u8>, boundary: &String,
                                                form_part: &mut FormPart, form_part_limit: Option<&FormPartLimit>)
                                                -> Result<FormPartResult, MultipartFormDataError> {
         let value_end_matcher = format!("\r\n--{}", boundary);
@@ -1093,13 +1763,14 @@ pub mod multipart {
 
                     bytes_written += to_copy_bytes.len();
                     value_buffer.extend(to_copy_bytes);
-
-                    // Remove partial value end boundary from body buffer
-                    *body_buffer = Vec::from(&body_buffer[end_index + value_end_matching_bytes.len()..]);
                 }
 
+                // Remove partial value end boundary from body buffer. Empty value still has the
+                // boundary to remove.
+                *body_buffer = Vec::from(&body_buffer[end_index + value_end_matching_bytes.len()..]);
+
                 // Check if the value bytes written is larger than the limit specified
-                if max_value_size.is_some() && max_value_size.unwrap() > bytes_written {
+                if max_value_size.is_some() && bytes_written > max_value_size.unwrap() {
                     return Err(MultipartFormDataError::MaxFieldSizeExceed(
                         form_part.name.clone().unwrap().to_string(),
                         "The form field value size exceeds the limit specified",
@@ -1116,24 +1787,10 @@ pub mod multipart {
                 // Read exact 4 bytes if there is nothing in the body buffer else request required number of bytes.
                 // 4 bytes should be there before completing request body.
 
-                if body_buffer.len() < 4 {
-                    // Amount of bytes to read
-                    let bytes_to_read = 4 - body_buffer.len();
-
-                    let request_new_chunk = reader.get_exact(bytes_to_read);
-                    match request_new_chunk {
-                        Ok(chunk) => {
-                            body_buffer.extend(chunk);
-                        }
-                        Err(error) => {
-                            return Err(error);
-                        }
-                    }
-                }
+                fill_body_buffer(reader, body_buffer, 4)?;
 
                 // Compare --\r\n
-                let body_end_compare = &body_buffer[0..4];
-                if body_end_compare == end_body_bytes {
+                if body_buffer.starts_with(end_body_bytes) {
                     // All form part has been parsed
                     body_buffer.clear();
                     form_part.value = Some(value_buffer);
@@ -1141,8 +1798,7 @@ pub mod multipart {
                 }
 
                 // Compare \r\n
-                let form_part_next_compare = &body_buffer[0..2];
-                if form_part_next_compare == next_part_bytes {
+                if body_buffer.starts_with(next_part_bytes) {
                     // Remove \r\n bytes from the body buffer
                     *body_buffer = Vec::from(&body_buffer[2..]);
                     form_part.value = Some(value_buffer);
@@ -1168,14 +1824,14 @@ pub mod multipart {
                     *body_buffer = Vec::from(&body_buffer[to_copy_size as usize..]);
                 }
 
-                if form_part_limit.is_some() && (bytes_written > form_part_limit.unwrap().max_size.unwrap()) {
+                if exceeds_part_limit(form_part_limit, bytes_written) {
                     return Err(MultipartFormDataError::MaxFieldSizeExceed(
                         form_part.name.clone().unwrap().to_string(),
                         "The form field value size exceeds the limit specified")
                     );
                 }
 
-                let request_new_chunk = reader.get_chunk();
+                let request_new_chunk = reader.get_chunk().map_err(Into::into);
                 match request_new_chunk {
                     Ok(chunk) => {
                         body_buffer.extend(chunk);
@@ -1196,11 +1852,16 @@ mod test {
     use std::io::{Read};
     use rand::{Rng};
     use crate::headers::Headers;
+    use crate::parser::{build_query, parse_url_encoded, parse_url_encoded_with_options, url_decode, url_encode, UrlEncodedOptions};
+    use crate::parser::reader::ReadError;
     use crate::parser::multipart::{StreamReader};
     use crate::parser::multipart::{
+        extract_form_file_body,
         extract_form_part_body,
+        extract_form_part_header,
         extract_form_value,
         FormPart,
+        FormPartLimit,
         Limits,
         MultipartFormDataError,
         parse,
@@ -1238,6 +1899,8 @@ mod test {
     }
 
     impl StreamReader for ChunkReader {
+        type Error = MultipartFormDataError;
+
         fn get_chunk(&mut self) -> Result<Vec<u8>, MultipartFormDataError> {
             // Number of bytes that are left
             let bytes_left = self.get_bytes_left();
@@ -1264,6 +1927,48 @@ mod test {
         }
     }
 
+    /// Returns a single byte for every read
+    struct ByteReader {
+        body_bytes: Vec<u8>,
+        bytes_read: usize,
+    }
+
+    impl StreamReader for ByteReader {
+        type Error = MultipartFormDataError;
+
+        fn get_chunk(&mut self) -> Result<Vec<u8>, MultipartFormDataError> {
+            return self.get_exact(1);
+        }
+
+        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, MultipartFormDataError> {
+            if self.bytes_read + size > self.body_bytes.len() {
+                return Err(MultipartFormDataError::BodyReadEnd);
+            }
+
+            let chunk = self.body_bytes[self.bytes_read..self.bytes_read + size].to_vec();
+            self.bytes_read += size;
+            return Ok(chunk);
+        }
+    }
+
+    /// Returns the bytes left even if they are fewer than requested
+    struct ShortReader {
+        body_bytes: Vec<u8>,
+    }
+
+    impl StreamReader for ShortReader {
+        type Error = MultipartFormDataError;
+
+        fn get_chunk(&mut self) -> Result<Vec<u8>, MultipartFormDataError> {
+            return self.get_exact(self.body_bytes.len());
+        }
+
+        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, MultipartFormDataError> {
+            let size = size.min(self.body_bytes.len());
+            return Ok(self.body_bytes.drain(..size).collect());
+        }
+    }
+
     const SAMPLE_BODY: &str = "----------------------------211628740782087473305609\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nJohn Doe\r\n----------------------------211628740782087473305609\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\n\r\n----------------------------211628740782087473305609\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\n\r\n----------------------------211628740782087473305609\r\nContent-Disposition: form-data; name=\"gender\"\r\n\r\nmale\r\n----------------------------211628740782087473305609--\r\n";
     const SAMPLE_BODY_2: &str = "--boundary123\r\nContent-Disposition: form-data; name=\"field1\"\r\n\r\nvalue1\r\n\r\n--boundary123\r\nContent-Disposition: form-data; name=\"file\"; filename=\"example.txt\"\r\nContent-Type: text/plain\r\n\r\nThis is the content of the file.\r\n--boundary123\r\nContent-Disposition: form-data; name=\"field2\"\r\n\r\nvalue2\r\n--boundary123--\r\n";
 
@@ -1298,6 +2003,46 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_preamble_and_epilogue() {
+        let body = "This is the preamble.\r\nIt is ignored.\r\n--boundary123\r\n\
+                    Content-Disposition: form-data; name=\"field1\"\r\n\r\nvalue1\r\n--boundary123--\r\n\
+                    This is the epilogue.\r\n";
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=boundary123".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        for _ in 0..10 {
+            let mut reader = ChunkReader::new(body, 0);
+            let form_parts = parse(Vec::new(), &headers, &mut reader, Limits::none()).unwrap();
+
+            assert_eq!(1, form_parts.len());
+            assert_eq!(b"value1", form_parts[0].value.as_ref().unwrap().as_slice());
+            // Epilogue is read, so that it is not treated as the next request
+            assert_eq!(body.len(), reader.bytes_read);
+        }
+    }
+
+    #[test]
+    fn test_leading_crlf() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=boundary123".to_string()]);
+
+        for leading in ["\r\n", "\r\n\r\n"] {
+            let body = format!("{}{}", leading, SAMPLE_BODY_2);
+
+            for _ in 0..10 {
+                let reader = ChunkReader::new(&body, 0);
+                let form_parts = parse(Vec::new(), &headers, reader, Limits::none()).unwrap();
+
+                assert_eq!(3, form_parts.len());
+                assert_eq!(b"value1", form_parts[0].value.as_ref().unwrap().as_slice());
+                assert_eq!(b"value2", form_parts[2].value.as_ref().unwrap().as_slice());
+            }
+        }
+    }
+
     #[test]
     fn test_header_parser() {
         let header_sample_1 = "\r\nContent-Disposition: form-data; name=\"John Doe\"\r\n\r\n";
@@ -1317,6 +2062,79 @@ mod test {
         assert_eq!(form_part.content_type.unwrap(), "text/plain");
     }
 
+    #[test]
+    fn test_form_part_header_split_across_reads() {
+        let part_header = "Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\nContent-Type: text/plain";
+        let sample_body = format!("{}\r\n\r\nhello", part_header);
+
+        for _ in 0..100 {
+            let mut reader = ChunkReader::new(&sample_body, 0);
+            let mut body_buffer = Vec::new();
+
+            let header = extract_form_part_header(&mut reader, &mut body_buffer, &Limits::none()).unwrap();
+            assert_eq!(part_header.as_bytes(), header.as_slice());
+            assert_eq!(true, b"hello".starts_with(&body_buffer));
+
+            let form_part = parse_form_part_header(String::from_utf8(header).unwrap()).unwrap();
+            assert_eq!(Some("file".to_string()), form_part.name);
+            assert_eq!(Some("a.txt".to_string()), form_part.filename);
+            assert_eq!(Some("text/plain".to_string()), form_part.content_type);
+        }
+    }
+
+    #[test]
+    fn test_form_part_header_byte_by_byte() {
+        // Line breaks near the end of the header make the partial \r\n\r\n matches
+        let part_header = "Content-Disposition: form-data; name=\"a\"\r\nX-A: \r\r\n\r\nX-B: b\r\n\r";
+        let sample_body = format!("{}\r\n\r\nvalue\r\n--boundary--\r\n", part_header);
+
+        let mut reader = ByteReader { body_bytes: sample_body.into_bytes(), bytes_read: 0 };
+        let mut body_buffer = Vec::new();
+
+        let header = extract_form_part_header(&mut reader, &mut body_buffer, &Limits::none()).unwrap();
+        assert_eq!("Content-Disposition: form-data; name=\"a\"\r\nX-A: \r", String::from_utf8(header).unwrap());
+
+        let header = extract_form_part_header(&mut reader, &mut body_buffer, &Limits::none()).unwrap();
+        assert_eq!("X-B: b\r\n\r", String::from_utf8(header).unwrap());
+        assert_eq!(true, b"value".starts_with(&body_buffer));
+
+        // Header size is still checked while reading byte by byte
+        let mut reader = ByteReader { body_bytes: format!("{}\r\n\r\n", "a".repeat(100)).into_bytes(), bytes_read: 0 };
+        let limits = Limits::builder().max_header_size(50).build();
+        let result = extract_form_part_header(&mut reader, &mut Vec::new(), &limits);
+        assert_eq!(true, matches!(result, Err(MultipartFormDataError::HeaderSizeExceed(_))));
+    }
+
+    #[test]
+    fn test_short_chunk_at_body_end() {
+        let boundary = "boundary123".to_string();
+
+        // Closing boundary is cut after `--`
+        for body_end in ["value\r\n--boundary123--", "value\r\n--boundary123-", "value\r\n--boundary123"] {
+            let mut form_part = FormPart::empty();
+            let mut reader = ShortReader { body_bytes: body_end.as_bytes().to_vec() };
+            let mut body_buffer = reader.get_exact(7).unwrap();
+
+            let result = extract_form_value(&mut reader, &mut body_buffer, &boundary, &mut form_part, None);
+            assert_eq!(true, matches!(result, Err(MultipartFormDataError::BodyReadEnd)));
+
+            form_part.filename = Some("a.txt".to_string());
+            let mut reader = ShortReader { body_bytes: body_end.as_bytes().to_vec() };
+            let mut body_buffer = reader.get_exact(7).unwrap();
+
+            let result = extract_form_file_body(&mut reader, &mut body_buffer, &boundary, &mut form_part, None);
+            assert_eq!(true, matches!(result, Err(MultipartFormDataError::BodyReadEnd)));
+        }
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=boundary123".to_string()]);
+        let body = "--boundary123\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n--boundary123--";
+        let reader = ShortReader { body_bytes: body.as_bytes().to_vec() };
+
+        let result = parse(Vec::new(), &headers, reader, Limits::none());
+        assert_eq!(true, matches!(result, Err(MultipartFormDataError::IncompleteBody(_))));
+    }
+
     #[test]
     fn test_extract_file_body() {
         let sample_body = "John Doe\r\n\r\n----------------------------163905767229441796406063\r\nContent-Disposition...";
@@ -1378,4 +2196,348 @@ mod test {
         assert_eq!(true, result.is_ok());
         assert_eq!(b"John Doe", &form_part.value.unwrap().as_slice());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_limits_builder() {
+        let limits = Limits::builder()
+            .max_body_size(1024)
+            .max_header_size(512)
+            .max_value_size(20)
+            .part_limit("file", FormPartLimit { max_size: Some(10), content_type: None })
+            .build();
+
+        assert_eq!(Some(1024), limits.max_body_size);
+        assert_eq!(Some(512), limits.max_header_size);
+        assert_eq!(Some(20), limits.max_value_size);
+        assert_eq!(Some(10), limits.form_part_limits["file"].max_size);
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=boundary123".to_string()]);
+
+        // File content is longer than the part limit
+        let reader = ChunkReader::new(SAMPLE_BODY_2, 0);
+        let result = parse(Vec::new(), &headers, reader, limits);
+        match result {
+            Err(MultipartFormDataError::MaxFieldSizeExceed(name, _)) => assert_eq!("file", name),
+            _ => panic!("Expected MaxFieldSizeExceed error"),
+        }
+
+        let limits = Limits::builder()
+            .part_limit("file", FormPartLimit { max_size: Some(100), content_type: None })
+            .build();
+        let reader = ChunkReader::new(SAMPLE_BODY_2, 0);
+        assert_eq!(3, parse(Vec::new(), &headers, reader, limits).unwrap().len());
+    }
+
+    #[test]
+    fn test_content_length_smaller_than_body() {
+        let mut headers: Headers = HashMap::new();
+        let content_type = vec!["multipart/form-data; boundary=boundary123".to_string()];
+        headers.insert("Content-Type".to_string(), content_type);
+
+        // Body is cut in the middle of the last form part
+        let content_length = SAMPLE_BODY_2.len() - 20;
+        headers.insert("Content-Length".to_string(), vec![content_length.to_string()]);
+
+        let partial_body = SAMPLE_BODY_2.as_bytes().to_vec();
+        let reader = ChunkReader::new(SAMPLE_BODY_2, SAMPLE_BODY_2.len());
+        let parse_result = parse(partial_body, &headers, reader, Limits::none());
+
+        match parse_result {
+            Err(MultipartFormDataError::IncompleteBody(_)) => {}
+            _ => panic!("Expected IncompleteBody error"),
+        }
+    }
+
+    #[test]
+    fn test_url_encoded_separators() {
+        let params = parse_url_encoded("a=1&b=2;3");
+        assert_eq!(vec!["1"], params["a"]);
+        assert_eq!(vec!["2;3"], params["b"]);
+
+        let options = UrlEncodedOptions {
+            separators: vec!['&', ';'],
+            ..UrlEncodedOptions::default()
+        };
+
+        let params = parse_url_encoded_with_options("a=1;b=2", &options);
+        assert_eq!(vec!["1"], params["a"]);
+        assert_eq!(vec!["2"], params["b"]);
+
+        let params = parse_url_encoded_with_options("a=1;b=2&a=3", &options);
+        assert_eq!(vec!["1", "3"], params["a"]);
+        assert_eq!(vec!["2"], params["b"]);
+    }
+
+    #[test]
+    fn test_url_encoded_array_brackets() {
+        let params = parse_url_encoded("tags[]=x&tags[]=y");
+        assert_eq!(vec!["x", "y"], params["tags[]"]);
+
+        let options = UrlEncodedOptions {
+            strip_array_brackets: true,
+            ..UrlEncodedOptions::default()
+        };
+
+        let params = parse_url_encoded_with_options("tags[]=x&tags%5B%5D=y&tags=z&[]=empty", &options);
+        assert_eq!(vec!["x", "y", "z"], params["tags"]);
+        assert_eq!(vec!["empty"], params["[]"]);
+
+        let params = parse_url_encoded_with_options("id=1&id=2", &options);
+        assert_eq!(vec!["1", "2"], params["id"]);
+    }
+
+    #[test]
+    fn test_build_query() {
+        assert_eq!("", build_query(&[]));
+        assert_eq!("name=John&age=22", build_query(&[("name", "John"), ("age", "22")]));
+        assert_eq!("q=a%26b%3Dc%20d&empty=", build_query(&[("q", "a&b=c d"), ("empty", "")]));
+        assert_eq!("na%C3%AFve%5B%5D=%E2%9C%93", build_query(&[("naïve[]", "✓")]));
+
+        let query = build_query(&[("q", "a&b=c d"), ("q", "100%"), ("empty", "")]);
+        let params = parse_url_encoded(&query);
+        assert_eq!(vec!["a&b=c d", "100%"], params["q"]);
+        assert_eq!(vec![""], params["empty"]);
+    }
+
+    #[test]
+    fn test_url_encode() {
+        assert_eq!("abc-._~123", url_encode("abc-._~123"));
+        assert_eq!("a%20b%2Fc%3Fd%3De%26f", url_encode("a b/c?d=e&f"));
+        assert_eq!("r%C3%A9sum%C3%A9", url_encode("résumé"));
+        assert_eq!("", url_encode(""));
+
+        for value in ["", "plain", "a b+c", "100%", "/path?x=1&y=2#top", "résumé ✓"] {
+            assert_eq!(value, url_decode(&url_encode(value)));
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(bytes).unwrap();
+        return encoder.finish().unwrap();
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_decode_limits() {
+        use crate::parser::content_encoding::{decode, DecodeError};
+
+        // 16 MiB of zeros compresses to a few KiB
+        let payload = vec![0u8; 16 * 1024 * 1024];
+        let encoded = gzip(&payload);
+        assert_eq!(true, encoded.len() < 64 * 1024);
+
+        assert_eq!(Err(DecodeError::MaxSizeExceed), decode("gzip", &encoded, 1024 * 1024, None));
+        assert_eq!(Err(DecodeError::MaxRatioExceed), decode("gzip", &encoded, usize::MAX, Some(100)));
+        assert_eq!(payload.len(), decode("gzip", &encoded, payload.len(), None).unwrap().len());
+        assert_eq!(Err(DecodeError::Unsupported("br".to_string())), decode("br", &encoded, usize::MAX, None));
+    }
+
+    #[test]
+    fn test_boundary_validation() {
+        use crate::parser::multipart::{extract_boundary, validate_boundary};
+
+        let content_type = "multipart/form-data; charset=utf-8; boundary=\"abc'()+_,-./:=? xyz\"".to_string();
+        let boundary = extract_boundary(&content_type).unwrap();
+        assert_eq!("abc'()+_,-./:=? xyz", boundary);
+        assert_eq!(true, validate_boundary(&boundary).is_ok());
+
+        assert_eq!(Some("".to_string()), extract_boundary(&"multipart/form-data; boundary=".to_string()));
+        assert_eq!(true, validate_boundary("").is_err());
+        assert_eq!(true, validate_boundary(&"a".repeat(70)).is_ok());
+        assert_eq!(true, validate_boundary(&"a".repeat(80)).is_err());
+        assert_eq!(true, validate_boundary("abc{}").is_err());
+        assert_eq!(true, validate_boundary("abc ").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid_boundary() {
+        let mut headers: Headers = HashMap::new();
+        let content_type = format!("multipart/form-data; boundary={}", "a".repeat(80));
+        headers.insert("Content-Type".to_string(), vec![content_type]);
+
+        let reader = ChunkReader::new("", 0);
+        let result = parse(Vec::new(), &headers, reader, Limits::none());
+        assert_eq!(true, matches!(result, Err(MultipartFormDataError::InvalidMultiPart(_))));
+    }
+
+    #[test]
+    fn test_boxed_reader() {
+        let body = SAMPLE_BODY_2;
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=boundary123".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        // Reader selected at runtime
+        let reader: Box<dyn StreamReader<Error = MultipartFormDataError>> = Box::new(ChunkReader::new(body, 0));
+        let form_parts = parse(Vec::new(), &headers, reader, Limits::none()).unwrap();
+        assert_eq!(Some("field1".to_string()), form_parts[0].name);
+
+        let mut reader = ChunkReader::new(body, 0);
+        let reader: &mut dyn StreamReader<Error = MultipartFormDataError> = &mut reader;
+        let form_parts = parse(Vec::new(), &headers, reader, Limits::none()).unwrap();
+        assert_eq!(3, form_parts.len());
+    }
+
+    /// Reader which can be used with all the parsers
+    struct SliceReader {
+        bytes: Vec<u8>,
+        position: usize,
+    }
+
+    impl crate::parser::reader::StreamReader for SliceReader {
+        type Error = ReadError;
+
+        fn get_chunk(&mut self) -> Result<Vec<u8>, ReadError> {
+            if self.position >= self.bytes.len() {
+                return Err(ReadError::BodyReadEnd);
+            }
+
+            let end = (self.position + 7).min(self.bytes.len());
+            let chunk = self.bytes[self.position..end].to_vec();
+            self.position = end;
+            return Ok(chunk);
+        }
+
+        fn get_exact(&mut self, size: usize) -> Result<Vec<u8>, ReadError> {
+            if self.position + size > self.bytes.len() {
+                return Err(ReadError::BodyReadEnd);
+            }
+
+            let chunk = self.bytes[self.position..self.position + size].to_vec();
+            self.position += size;
+            return Ok(chunk);
+        }
+    }
+
+    fn slice_reader(body: &str) -> SliceReader {
+        return SliceReader {
+            bytes: body.as_bytes().to_vec(),
+            position: 0,
+        };
+    }
+
+    #[test]
+    fn test_reader_for_all_parsers() {
+        use crate::parser::{body, url_encoded};
+
+        let body_text = "name=John&age=22";
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Length".to_string(), vec![body_text.len().to_string()]);
+
+        let mut temp_file = body::parse(Vec::new(), &headers, slice_reader(body_text)).unwrap();
+        let mut content = String::new();
+        temp_file.read_to_string(&mut content).unwrap();
+        assert_eq!(body_text, content);
+
+        let limits = url_encoded::Limits {
+            max_body_size: 1024,
+        };
+        let form_fields = url_encoded::parse(Vec::new(), &headers, &mut slice_reader(body_text), limits).unwrap();
+        assert_eq!(Some(&vec!["22".to_string()]), form_fields.get("age"));
+
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=boundary123".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![SAMPLE_BODY_2.len().to_string()]);
+        let form_parts = parse(Vec::new(), &headers, slice_reader(SAMPLE_BODY_2), Limits::none()).unwrap();
+        assert_eq!(3, form_parts.len());
+        assert_eq!(Some("field1".to_string()), form_parts[0].name);
+    }
+
+    #[test]
+    fn test_content_disposition_filename() {
+        use crate::parser::multipart::{parse_content_disposition_value, FormPart};
+
+        let mut form_part = FormPart::empty();
+        parse_content_disposition_value(r#"form-data; name="file"; filename="report.pdf""#, &mut form_part);
+        assert_eq!(Some("file".to_string()), form_part.name);
+        assert_eq!(Some("report.pdf".to_string()), form_part.filename);
+
+        let mut form_part = FormPart::empty();
+        let value = r#"form-data; name="file"; filename="r_sum_.pdf"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"#;
+        parse_content_disposition_value(value, &mut form_part);
+        assert_eq!(Some("résumé.pdf".to_string()), form_part.filename);
+
+        // Browsers send quotes percent encoded
+        let mut form_part = FormPart::empty();
+        parse_content_disposition_value(r#"form-data; name="file"; filename="a%22b%20c.txt""#, &mut form_part);
+        assert_eq!(Some("a\"b%20c.txt".to_string()), form_part.filename);
+
+        let mut form_part = FormPart::empty();
+        parse_content_disposition_value(r#"form-data; name="a\"b"; filename="c\\d.txt""#, &mut form_part);
+        assert_eq!(Some("a\"b".to_string()), form_part.name);
+        assert_eq!(Some("c\\d.txt".to_string()), form_part.filename);
+
+        // Attribute with hyphen is not confused with the name
+        let mut form_part = FormPart::empty();
+        parse_content_disposition_value(r#"form-data; name="right"; x-name="wrong""#, &mut form_part);
+        assert_eq!(Some("right".to_string()), form_part.name);
+    }
+
+    #[test]
+    fn test_error_display() {
+        use std::error::Error;
+        use crate::parser::body::BodyReadError;
+        use crate::parser::content_encoding::DecodeError;
+        use crate::parser::url_encoded::UrlEncodedFormDataError;
+
+        assert_eq!("Content-Length header is missing", BodyReadError::ContentLengthMissing.to_string());
+        assert_eq!("Missing boundary", MultipartFormDataError::InvalidMultiPart("Missing boundary").to_string());
+        assert_eq!("Body ended before it was fully parsed", UrlEncodedFormDataError::BodyReadEnd.to_string());
+
+        let error = BodyReadError::Decode(DecodeError::Unsupported("br".to_string()));
+        assert_eq!("Unable to decode body: Unsupported content encoding: br", error.to_string());
+        assert_eq!("Unsupported content encoding: br", error.source().unwrap().to_string());
+        assert_eq!(true, BodyReadError::BodyAlreadyRead.source().is_none());
+
+        let errors: Vec<Box<dyn Error>> = vec![
+            Box::new(ReadError::MaxSizeExceed),
+            Box::new(BodyReadError::MaxBodySizeExceed),
+            Box::new(DecodeError::MaxRatioExceed),
+            Box::new(UrlEncodedFormDataError::MaxBodySizeExceed("Maximum body size exceed")),
+            Box::new(MultipartFormDataError::BodyReadEnd),
+            Box::new(BodyReadError::from(ReadError::Cancelled)),
+        ];
+
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(vec![
+            "Maximum body size exceed",
+            "Maximum body size exceed",
+            "Decoded body is larger than the allowed ratio",
+            "Maximum body size exceed",
+            "Body ended before it was fully parsed",
+            "Body read is cancelled",
+        ], messages);
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        use std::io::Cursor;
+        use crate::parser::body::BodyReadError;
+        use crate::parser::transfer_encoding::{codings, decode_chunked, validate_codings};
+
+        let buffer = b"5;name=value\r\nhello\r\n6\r\n wor".to_vec();
+        let mut reader = Cursor::new(b"ld\r\n0\r\nExpires: never\r\n\r\nGET / HTTP/1.1\r\n".to_vec());
+        let (body, leftover) = decode_chunked(buffer, &mut reader, 1024).unwrap();
+        assert_eq!(b"hello world".to_vec(), body);
+        assert_eq!(b"GET / HTTP/1.1\r\n".to_vec(), leftover);
+
+        let mut reader = Cursor::new(Vec::new());
+        let result = decode_chunked(b"zz\r\n".to_vec(), &mut reader, 1024);
+        assert_eq!(true, matches!(result, Err(BodyReadError::InvalidTransferEncoding(_))));
+
+        let result = decode_chunked(b"5\r\nhello\r\n0\r\n\r\n".to_vec(), &mut reader, 4);
+        assert_eq!(true, matches!(result, Err(BodyReadError::MaxBodySizeExceed)));
+
+        let values = vec!["gzip, Chunked".to_string()];
+        assert_eq!(vec!["gzip", "chunked"], codings(&values));
+        assert_eq!(true, validate_codings(&codings(&values)).is_ok());
+        assert_eq!(true, validate_codings(&codings(&["chunked, gzip".to_string()])).is_err());
+        assert_eq!(true, validate_codings(&codings(&["chunked".to_string(), "chunked".to_string()])).is_err());
+    }
+}
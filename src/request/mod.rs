@@ -1,20 +1,84 @@
 use std::collections::HashMap;
-use std::net::TcpStream;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::net::{IpAddr, TcpStream};
 use std::sync::{Arc};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tempfile::NamedTempFile;
 use crate::headers;
 use crate::headers::{Headers};
-use crate::parser::{body, multipart, url_encoded};
-use crate::parser::body::Limits;
+use crate::parser::{body, content_encoding, multipart, parse_url_encoded_with_options, transfer_encoding, url_encoded,
+                    UrlEncodedOptions};
+use crate::parser::body::{BodyReadError, Limits};
 use crate::parser::body::reader::BodyReader;
 use crate::parser::multipart::{FormPart, MultipartFormDataError};
 use crate::parser::multipart::reader::FormDataReader;
+use crate::parser::reader::{ProgressCallback, ProgressReader, StreamReader};
 use crate::parser::url_encoded::{FormFields, UrlEncodedFormDataError};
 use crate::parser::url_encoded::reader::UrlEncodedReader;
-use crate::request::form::{FormFiles, FormData, FormFile};
+use crate::paths::RouteConfig;
+use crate::request::form::{DuplicatePolicy, FormError, FormFiles, FormData, FormDataBytes, FormFile, FormTree,
+                           MapFirstStringMethod};
 use crate::server::Context;
 
+/// Error of reading or parsing the request body by any of the body parsers
+#[derive(Debug)]
+pub enum RequestError {
+    Body(BodyReadError),
+    UrlEncoded(UrlEncodedFormDataError),
+    Multipart(MultipartFormDataError),
+    /// Body is sent without `Content-Type`, so it can't be parsed as form data
+    MissingContentType,
+    /// Body is not form data. For example: `application/json`. It can be read using `body`.
+    UnsupportedContentType(String),
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            RequestError::Body(error) => write!(f, "{}", error),
+            RequestError::UrlEncoded(error) => write!(f, "{}", error),
+            RequestError::Multipart(error) => write!(f, "{}", error),
+            RequestError::MissingContentType => write!(f, "Body has content, but missing content type"),
+            RequestError::UnsupportedContentType(content_type) => {
+                write!(f, "Content type {} is not form data. Read it using body instead", content_type)
+            }
+        };
+    }
+}
+
+impl Error for RequestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return match self {
+            RequestError::Body(error) => Some(error),
+            RequestError::UrlEncoded(error) => Some(error),
+            RequestError::Multipart(error) => Some(error),
+            RequestError::MissingContentType | RequestError::UnsupportedContentType(_) => None,
+        };
+    }
+}
+
+impl From<BodyReadError> for RequestError {
+    fn from(error: BodyReadError) -> Self {
+        return RequestError::Body(error);
+    }
+}
+
+impl From<UrlEncodedFormDataError> for RequestError {
+    fn from(error: UrlEncodedFormDataError) -> Self {
+        return RequestError::UrlEncoded(error);
+    }
+}
+
+impl From<MultipartFormDataError> for RequestError {
+    fn from(error: MultipartFormDataError) -> Self {
+        return RequestError::Multipart(error);
+    }
+}
+
 fn map_first_vec_value(map: &HashMap<String, Vec<String>>, key: &str) -> Option<String> {
     if let Some(values) = map.get(key) {
         if values.len() > 0 {
@@ -27,28 +91,157 @@ fn map_first_vec_value(map: &HashMap<String, Vec<String>>, key: &str) -> Option<
 }
 
 pub mod form {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
+    use std::error::Error;
+    use std::fmt::{Display, Formatter};
+    use std::{fs, io};
+    use std::io::Read;
+    use std::path::Path;
     use tempfile::NamedTempFile;
     use crate::request::map_first_vec_value;
 
     pub struct FormFile {
         pub filename: String,
+        /// Content type declared by the client. Use `sniff_mime` to verify the actual type.
+        pub content_type: Option<String>,
         pub temp_file: NamedTempFile,
     }
 
+    /// Leading bytes of the common file types
+    const FILE_SIGNATURES: [(&[u8], &str); 8] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"PK\x05\x06", "application/zip"),
+        (b"PK\x07\x08", "application/zip"),
+    ];
+
+    impl FormFile {
+        /// Copies the uploaded file to the given path and returns the number of bytes copied.
+        /// Temporary file is kept, so it can be saved multiple times.
+        pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<u64> {
+            return fs::copy(self.temp_file.path(), path);
+        }
+
+        /// Returns the mime type detected from the leading bytes of the uploaded file.
+        /// Supports PNG, JPEG, GIF, PDF and ZIP. Returns `None` if the type is unknown.
+        pub fn sniff_mime(&self) -> Option<&'static str> {
+            let mut file = self.temp_file.reopen().ok()?;
+            let mut leading_bytes = Vec::new();
+            file.by_ref().take(8).read_to_end(&mut leading_bytes).ok()?;
+
+            for (signature, mime) in FILE_SIGNATURES {
+                if leading_bytes.starts_with(signature) {
+                    return Some(mime);
+                }
+            }
+
+            return None;
+        }
+
+        /// Moves the uploaded file to the given path. Temporary file is renamed without copying
+        /// if the path is in the same file system. Otherwise, it is copied.
+        pub fn persist<P: AsRef<Path>>(self, path: P) -> io::Result<()> {
+            let path = path.as_ref();
+
+            match self.temp_file.persist(path) {
+                Ok(_) => {
+                    return Ok(());
+                }
+
+                Err(error) => {
+                    // Rename fails across the file systems
+                    fs::copy(error.file.path(), path)?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
     pub type MapFirstString = HashMap<String, Vec<String>>;
 
+    #[derive(Debug, PartialEq)]
+    pub enum FormError {
+        /// Occurs, if the field is not present
+        Missing(String),
+        /// Occurs, if the field has more than one value
+        MultipleValues(String),
+    }
+
+    impl Display for FormError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            return match self {
+                FormError::Missing(name) => write!(f, "Field {} is missing", name),
+                FormError::MultipleValues(name) => write!(f, "Field {} has multiple values", name),
+            };
+        }
+    }
+
+    impl Error for FormError {}
+
+    /// Decides which value is used if the same parameter is sent multiple times
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum DuplicatePolicy {
+        /// Uses the first value
+        #[default]
+        First,
+        /// Uses the last value
+        Last,
+        /// Returns `FormError::MultipleValues`
+        Reject,
+    }
+
     pub trait MapFirstStringMethod {
+        /// Returns the first value of the field
         fn value(&self, name: &str) -> Option<String>;
+        /// Returns the value only if the field has exactly one value. It protects from
+        /// parameter pollution where the same field is sent multiple times.
+        fn single(&self, name: &str) -> Result<String, FormError>;
+        /// Returns the value of the field according to the duplicate policy
+        fn value_with_policy(&self, name: &str, policy: DuplicatePolicy) -> Result<Option<String>, FormError>;
     }
 
     impl MapFirstStringMethod for MapFirstString {
         fn value(&self, name: &str) -> Option<String> {
             return map_first_vec_value(self, name);
         }
+
+        fn single(&self, name: &str) -> Result<String, FormError> {
+            return match self.get(name).map(|values| values.as_slice()) {
+                Some([value]) => Ok(value.to_owned()),
+                Some([]) | None => Err(FormError::Missing(name.to_string())),
+                Some(_) => Err(FormError::MultipleValues(name.to_string())),
+            };
+        }
+
+        fn value_with_policy(&self, name: &str, policy: DuplicatePolicy) -> Result<Option<String>, FormError> {
+            let values = match self.get(name) {
+                Some(values) => values,
+                None => {
+                    return Ok(None);
+                }
+            };
+
+            return match policy {
+                DuplicatePolicy::First => Ok(values.first().cloned()),
+                DuplicatePolicy::Last => Ok(values.last().cloned()),
+                DuplicatePolicy::Reject => {
+                    if values.len() > 1 {
+                        return Err(FormError::MultipleValues(name.to_string()));
+                    }
+
+                    Ok(values.first().cloned())
+                }
+            };
+        }
     }
 
     pub type FormData = HashMap<String, Vec<String>>;
+    /// Form field values as bytes. Multipart values which are not valid UTF-8 are kept as it is.
+    pub type FormDataBytes = HashMap<String, Vec<Vec<u8>>>;
     pub type FormFiles = HashMap<String, Vec<FormFile>>;
 
     pub trait FormFileMethods {
@@ -68,6 +261,179 @@ pub mod form {
         }
     }
 
+    /// Required fields which are missing in the submitted form. See `validate_required`.
+    #[derive(Debug, PartialEq)]
+    pub struct ValidationReport {
+        pub missing: Vec<String>,
+    }
+
+    impl ValidationReport {
+        /// Returns true if all the required fields are present
+        pub fn is_valid(&self) -> bool {
+            return self.missing.is_empty();
+        }
+
+        /// Returns `FormError::Missing` for each missing field
+        pub fn errors(&self) -> Vec<FormError> {
+            return self.missing.iter().map(|name| FormError::Missing(name.to_string())).collect();
+        }
+    }
+
+    /// Checks the required fields in the parsed form data and files. Field is missing if it has
+    /// only empty values, or the file is submitted without filename.
+    pub fn validate_required(form_data: &FormData, form_files: &FormFiles, required: &[&str]) -> ValidationReport {
+        let mut missing = Vec::new();
+
+        for name in required {
+            let has_value = form_data.get(*name)
+                .is_some_and(|values| values.iter().any(|value| !value.is_empty()));
+            let has_file = form_files.get(*name)
+                .is_some_and(|files| files.iter().any(|file| !file.filename.is_empty()));
+
+            if !has_value && !has_file {
+                missing.push(name.to_string());
+            }
+        }
+
+        return ValidationReport { missing };
+    }
+
+    /// Value of the nested form field. See `form_tree`.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum FormValue {
+        Value(String),
+        /// Repeated values or the fields with numeric index. Example: `items[0][id]`
+        List(Vec<FormValue>),
+        Map(FormTree),
+    }
+
+    pub type FormTree = BTreeMap<String, FormValue>;
+
+    impl FormValue {
+        /// Returns the value if it is not nested
+        pub fn as_str(&self) -> Option<&str> {
+            return match self {
+                FormValue::Value(value) => Some(value.as_str()),
+                _ => None,
+            };
+        }
+
+        /// Returns the child by name for map or by index for list
+        pub fn get(&self, key: &str) -> Option<&FormValue> {
+            return match self {
+                FormValue::Map(map) => map.get(key),
+                FormValue::List(values) => key.parse::<usize>().ok().and_then(|index| values.get(index)),
+                FormValue::Value(_) => None,
+            };
+        }
+    }
+
+    /// Parses the bracketed and dotted field names into the nested values. For example:
+    /// `user[name]=John&user.age=22&items[0][id]=1&tags[]=a`. Field which conflicts with an
+    /// already parsed field of different shape is ignored.
+    pub fn form_tree(form_data: &FormData) -> FormTree {
+        let mut tree = FormTree::new();
+
+        // Sorted, so that the conflicting fields are resolved in the same way every time
+        let mut names: Vec<&String> = form_data.keys().collect();
+        names.sort();
+
+        for name in names {
+            let segments = split_field_name(name);
+            for value in &form_data[name] {
+                insert_form_value(&mut tree, &segments, value);
+            }
+        }
+
+        return tree.into_iter().map(|(name, value)| (name, numeric_maps_to_lists(value))).collect();
+    }
+
+    /// Splits `user[address][city]` or `user.address.city` into the segments. Empty segment
+    /// of `tags[]` appends the value. Malformed name is used as it is.
+    fn split_field_name(name: &str) -> Vec<String> {
+        let (head, mut rest) = match name.find('[') {
+            Some(index) => name.split_at(index),
+            None => (name, ""),
+        };
+
+        let mut segments: Vec<String> = head.split('.').map(|segment| segment.to_string()).collect();
+        while let Some(stripped) = rest.strip_prefix('[') {
+            let end = match stripped.find(']') {
+                Some(end) => end,
+                None => break,
+            };
+
+            segments.push(stripped[..end].to_string());
+            rest = &stripped[end + 1..];
+        }
+
+        if !rest.is_empty() || segments[0].is_empty() {
+            return vec![name.to_string()];
+        }
+
+        return segments;
+    }
+
+    fn insert_form_value(tree: &mut FormTree, segments: &[String], value: &str) {
+        let key = if segments[0].is_empty() { tree.len().to_string() } else { segments[0].to_string() };
+
+        if segments.len() == 1 {
+            match tree.get_mut(&key) {
+                None => {
+                    tree.insert(key, FormValue::Value(value.to_string()));
+                }
+
+                Some(FormValue::Value(existing)) => {
+                    let values = vec![FormValue::Value(existing.to_string()), FormValue::Value(value.to_string())];
+                    tree.insert(key, FormValue::List(values));
+                }
+
+                Some(FormValue::List(values)) => {
+                    values.push(FormValue::Value(value.to_string()));
+                }
+
+                Some(FormValue::Map(_)) => {}
+            }
+
+            return;
+        }
+
+        let child = tree.entry(key).or_insert_with(|| FormValue::Map(FormTree::new()));
+        if let FormValue::Map(child) = child {
+            insert_form_value(child, &segments[1..], value);
+        }
+    }
+
+    /// Maps with only numeric keys are converted to lists ordered by the index
+    fn numeric_maps_to_lists(value: FormValue) -> FormValue {
+        let mut map = match value {
+            FormValue::Map(map) => map,
+            value => return value,
+        };
+
+        let mut indexed = Vec::new();
+        for key in map.keys() {
+            match key.parse::<usize>() {
+                Ok(index) => indexed.push((index, key.to_string())),
+                Err(_) => {
+                    indexed.clear();
+                    break;
+                }
+            }
+        }
+
+        if indexed.is_empty() {
+            return FormValue::Map(map.into_iter().map(|(key, value)| (key, numeric_maps_to_lists(value))).collect());
+        }
+
+        indexed.sort();
+        let values = indexed.into_iter()
+            .filter_map(|(_, key)| map.remove(&key))
+            .map(numeric_maps_to_lists)
+            .collect();
+        return FormValue::List(values);
+    }
+
     pub struct File {
         pub name: String,
         pub content_type: String,
@@ -76,26 +442,59 @@ pub mod form {
 
 pub type QueryParams = HashMap<String, Vec<String>>;
 
+/// Maximum length of the incoming `X-Request-Id` which is reused
+const MAX_REQUEST_ID_LENGTH: usize = 200;
+
+/// Makes the generated request ids unique within the same microsecond
+static REQUEST_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
 pub struct Request {
     pub context: Arc<Context>,
-    pub stream: TcpStream,
+    /// Connection of the request. It is shared by the clones of the request and the response, so
+    /// the connection uses a single file descriptor for all of its requests.
+    pub stream: Arc<TcpStream>,
     pub method: String,
+    /// HTTP version of the request line. Example: `HTTP/1.1`
+    pub version: String,
+    /// Incoming `X-Request-Id` or the generated unique id. It is sent back in the response.
+    pub request_id: String,
     pub raw_path: String,
     pub pathname: String,
     pub query_params: QueryParams,
+    /// Values of the `:name` segments of the matched route. Example: `id` of `/users/:id`
+    pub path_params: HashMap<String, String>,
+    /// Path template of the matched route. Example: `/users/:id`
+    pub matched_route: Option<String>,
     pub headers: Headers,
+    /// Overrides the default maximum body size of `body`, `form_data` and `files`
+    pub max_body_size: Option<usize>,
+    /// Policy used by `query_value` and `form_value` for repeated parameters
+    pub duplicate_policy: DuplicatePolicy,
     pub partial_body: Option<Vec<u8>>,
+    /// Complete request body kept in memory. Available only after `retain_raw_body` succeeds.
+    raw_body: Option<Vec<u8>>,
     form_data: FormData,
+    form_data_bytes: FormDataBytes,
     form_files: FormFiles,
+    /// Error of parsing the body as form data. See `body_error`.
+    body_error: Option<RequestError>,
+    /// Header lines as received. Only kept if `Config::retain_raw_headers` is enabled.
+    raw_headers: Option<String>,
+    /// Called while the body is read. See `on_body_progress`.
+    body_progress: Option<ProgressCallback>,
+    /// Body reads are cancelled once it is set. See `set_body_cancel_flag`.
+    body_cancel: Option<Arc<AtomicBool>>,
     /// It specifies that body has been read already either some part or all.
     /// If body read is true, but body parse is false, need to change current connection to "keep-alive: close"
     /// It is because parsing body is probably failed.
     pub body_read: Arc<AtomicBool>,
     pub body_parsed: Arc<AtomicBool>,
+    /// Sequence of the request in the connection. See `Context::request_sequence`
+    pub sequence: usize,
 }
 
 impl Request {
-    pub fn new(context: Arc<Context>, stream: TcpStream, request_method: String, raw_path: String,
+    pub fn new<S: Into<Arc<TcpStream>>>(context: Arc<Context>, stream: S, request_method: String, raw_path: String,
                headers: HashMap<String, Vec<String>>, body_read: Arc<AtomicBool>,
                body_parsed: Arc<AtomicBool>) -> Self {
         let form_data = FormData::new();
@@ -103,23 +502,58 @@ impl Request {
 
         let pathname = Self::pathname_from_raw(&raw_path);
         let query_params = headers::query_params_from_raw(&raw_path);
+        let sequence = context.request_sequence.load(Ordering::Relaxed);
+        let request_id = Self::request_id_from_headers(&headers);
 
         return Request {
             context,
-            stream,
+            stream: stream.into(),
             method: request_method,
+            version: "HTTP/1.1".to_string(),
+            request_id,
             raw_path,
             pathname,
             query_params,
+            path_params: HashMap::new(),
+            matched_route: None,
             headers,
+            max_body_size: None,
+            duplicate_policy: DuplicatePolicy::default(),
             partial_body: None,
+            raw_body: None,
             form_data,
+            form_data_bytes: FormDataBytes::new(),
             form_files,
+            body_error: None,
+            raw_headers: None,
+            body_progress: None,
+            body_cancel: None,
             body_read,
             body_parsed,
+            sequence,
         };
     }
 
+    /// Reuses the incoming `X-Request-Id` if it is short and printable, otherwise generates
+    /// a new id from the current time and a counter.
+    fn request_id_from_headers(headers: &Headers) -> String {
+        if let Some(request_id) = headers.get("X-Request-Id").and_then(|values| values.first()) {
+            let request_id = request_id.trim();
+            let is_valid = !request_id.is_empty() && request_id.len() <= MAX_REQUEST_ID_LENGTH
+                && request_id.bytes().all(|byte| byte.is_ascii_graphic());
+
+            if is_valid {
+                return request_id.to_string();
+            }
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_micros())
+            .unwrap_or(0);
+        let count = REQUEST_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        return format!("{:x}-{:x}", timestamp, count);
+    }
+
     fn pathname_from_raw(raw_path: &String) -> String {
         if let Some(index) = raw_path.find("?") {
             let slice = &raw_path.as_str()[0..index];
@@ -129,67 +563,457 @@ impl Request {
         return raw_path.to_string();
     }
 
-    pub fn setup(&mut self) {
-        let content_length = headers::content_length(&self.headers);
-        let request_method = self.method.to_uppercase();
+    /// Applies the limits of the matched route before passing the request to the view
+    pub fn apply_route_config(&mut self, config: &RouteConfig) {
+        if let Some(max_body_size) = config.max_body_size {
+            self.max_body_size = Some(max_body_size);
+        }
 
-        if matches!(request_method.as_str(), "GET" | "HEAD" | "OPTIONS" | "DELETE" | "TRACE" | "CONNECT") {
-            // Same connections can be used fot these requests since there is no request body
-            if !content_length.is_some() {
-                // Content length is missing. Assuming there is no request body
-                self.body_read.store(true, Ordering::Relaxed);
+        if let Some(read_timeout) = config.read_timeout {
+            if !self.stream.set_read_timeout(Some(read_timeout)).is_ok() {
+                eprintln!("Failed to set read timeout");
             }
         }
     }
 
+    /// Returns the path template of the matched route. For example, `/users/:id` instead of
+    /// `/users/42`. It is useful for metrics labels.
+    pub fn matched_route(&self) -> Option<&str> {
+        return self.matched_route.as_deref();
+    }
+
+    /// Returns the value of the path parameter. Example: `id` of `/users/:id`
+    pub fn path_param(&self, name: &str) -> Option<&str> {
+        return self.path_params.get(name).map(|value| value.as_str());
+    }
+
+    /// Returns the untouched query string after `?`. It is useful for verifying signature of the
+    /// query string since the parsed query params are decoded.
+    pub fn raw_query(&self) -> Option<&str> {
+        if let Some(index) = self.raw_path.find("?") {
+            return Some(&self.raw_path[index + 1..]);
+        }
+
+        return None;
+    }
+
+    /// Parses the query params again with the given options. For example, to accept `;` as
+    /// separator along with `&`.
+    pub fn query_params_with_options(&self, options: &UrlEncodedOptions) -> QueryParams {
+        if let Some(raw_query) = self.raw_query() {
+            return parse_url_encoded_with_options(raw_query, options);
+        }
+
+        return QueryParams::new();
+    }
+
+    pub fn setup(&mut self) {
+        // Body presence is decided by the message framing, not by the request method.
+        // Without Content-Length and Transfer-Encoding there is no request body.
+        if !self.has_body() {
+            // Same connection can be used for the next request since there is nothing to read
+            self.body_read.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns true if the request framing headers specify a request body
+    pub fn has_body(&self) -> bool {
+        if self.headers.contains_key("Transfer-Encoding") {
+            return true;
+        }
+
+        return match headers::content_length(&self.headers) {
+            Some(content_length) => content_length > 0,
+            None => false,
+        };
+    }
+
+
+    /// Returns the value of `Content-Length` header if available
+    pub fn content_length(&self) -> Option<usize> {
+        return headers::content_length(&self.headers);
+    }
+
+    /// Returns the value of `Content-Type` header if available
+    pub fn content_type(&self) -> Option<&str> {
+        if let Some(values) = self.headers.get("Content-Type") {
+            return values.first().map(|value| value.as_str());
+        }
+
+        return None;
+    }
+
+    /// Returns the parameters of `Content-Type` header such as `charset` and `boundary`.
+    /// Parameter names are in lowercase.
+    pub fn content_type_params(&self) -> HashMap<String, String> {
+        let content_type = self.content_type().unwrap_or("");
+        let parameters = content_type.split_once(';').map(|(_, parameters)| parameters).unwrap_or("");
+        return headers::parse_parameters(parameters).into_iter().collect();
+    }
+
+    /// Returns the entity tags of `If-Match` header. Returns `None` if there is no header.
+    /// See `Response::check_if_match`.
+    pub fn if_match(&self) -> Option<Vec<String>> {
+        if !self.headers.contains_key("If-Match") {
+            return None;
+        }
+
+        return Some(headers::header_list(&self.headers, "If-Match"));
+    }
+
+    /// Returns the values of comma separated list header. For example: `Accept-Encoding: gzip, br`
+    /// returns `["gzip", "br"]`.
+    pub fn header_list(&self, name: &str) -> Vec<String> {
+        return headers::header_list(&self.headers, name);
+    }
+
+    /// Returns the IP address of the connected client
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        return self.stream.peer_addr().ok().map(|address| address.ip());
+    }
 
     pub fn set_partial_body_bytes(&mut self, bytes: Vec<u8>) {
         self.partial_body = Some(bytes);
     }
 
-    pub fn should_close_connection(&self) -> bool {
-        let connection_type = headers::connection_type(&self.headers);
+    pub fn set_raw_headers(&mut self, raw_headers: String) {
+        self.raw_headers = Some(raw_headers);
+    }
+
+    /// Returns the header lines as received, separated by `\r\n`. The request line is not
+    /// included. Only available if `Config::retain_raw_headers` is enabled.
+    pub fn raw_headers(&self) -> Option<&str> {
+        return self.raw_headers.as_deref();
+    }
+
+    /// Returns the header name and value pairs with the name casing as sent by the client. Pairs
+    /// are in the received order if `Config::retain_raw_headers` is enabled, otherwise the order
+    /// is unspecified.
+    pub fn headers_original_case(&self) -> impl Iterator<Item=(String, String)> {
+        let pairs: Vec<(String, String)> = match &self.raw_headers {
+            Some(raw_headers) => {
+                raw_headers.split("\r\n").filter_map(headers::parse_header).collect()
+            }
 
-        if let Some(connection_type) = &connection_type {
-            if connection_type.to_lowercase() == "keep-alive" && self.body_read.load(Ordering::Relaxed) {
-                return false;
+            None => {
+                self.headers.iter()
+                    .flat_map(|(name, values)| values.iter().map(move |value| (name.clone(), value.clone())))
+                    .collect()
             }
+        };
+
+        return pairs.into_iter();
+    }
+
+    /// Returns the first protocol of the `Upgrade` header if the client requested the upgrade
+    /// using `Connection: Upgrade`. Example: `websocket`
+    pub fn upgrade_protocol(&self) -> Option<String> {
+        if !headers::has_connection_token(&self.headers, "upgrade") {
+            return None;
         }
 
+        return headers::header_list(&self.headers, "Upgrade").into_iter().next();
+    }
+
+    pub fn should_close_connection(&self) -> bool {
+        // Connection is not reused, for example when the server is shutting down
+        if !self.context.accept_next.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        if headers::is_keep_alive(&self.headers) && self.body_read.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        return true;
+    }
+
+    /// Reads and drops the body which is not read by the view, so that the connection can be
+    /// reused for the next request. Only the body with `Content-Length` up to 1 MiB is discarded.
+    /// Returns false if the body can't be discarded, in which case the connection must be closed.
+    pub fn discard_body(&mut self) -> bool {
+        const MAX_DISCARD_SIZE: usize = 1024 * 1024; // 1 MiB
+
+        if self.body_read.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        // Length of the chunked body is unknown
+        if self.headers.contains_key("Transfer-Encoding") {
+            return false;
+        }
+
+        if self.read_full_body(MAX_DISCARD_SIZE).is_err() {
+            return false;
+        }
+
+        self.body_read.store(true, Ordering::Relaxed);
         return true;
     }
 
+    /// Reads the complete body to memory and keeps it, so that the exact raw bytes are available
+    /// from `raw_body` even after the body is parsed with `form_data`, `files` or `body`.
+    /// It is mainly used for verifying webhook signatures. Content-Length is required and must not
+    /// be larger than `max_size`. Call it before accessing the body in any other way.
+    pub fn retain_raw_body(&mut self, max_size: usize) -> Result<(), BodyReadError> {
+        if self.raw_body.is_some() {
+            return Ok(());
+        }
+
+        if self.body_read.load(Ordering::Relaxed) {
+            return Err(BodyReadError::BodyAlreadyRead);
+        }
+
+        let body_bytes = self.read_full_body(max_size)?;
+
+        // Parsers will read the body from the partial body instead of the stream
+        self.partial_body = Some(body_bytes.clone());
+        self.raw_body = Some(body_bytes);
+        return Ok(());
+    }
+
+    /// Reads the complete body of `Content-Length` size to memory
+    fn read_full_body(&mut self, max_size: usize) -> Result<Vec<u8>, BodyReadError> {
+        let content_length = match headers::content_length(&self.headers) {
+            Some(content_length) => content_length,
+            None => {
+                return Err(BodyReadError::ContentLengthMissing);
+            }
+        };
+
+        if content_length > max_size {
+            return Err(BodyReadError::MaxBodySizeExceed);
+        }
+
+        let mut body_bytes = self.partial_body.take().unwrap_or_default();
+        body_bytes.truncate(content_length);
+
+        let mut remaining = vec![0u8; content_length - body_bytes.len()];
+        if !self.stream.as_ref().read_exact(&mut remaining).is_ok() {
+            self.context.dont_wait();
+            return Err(BodyReadError::Others("Unable to read stream. May be client disconnected."));
+        }
+        body_bytes.extend(remaining);
+        return Ok(body_bytes);
+    }
+
+    /// Decodes the body sent with `Transfer-Encoding`. Chunked body is read to memory and the
+    /// other codings are decoded in the reverse order of applying. `Content-Length` is updated, so
+    /// the parsers receive the decoded body with known length.
+    fn decode_transfer_encoding(&mut self) -> Result<(), BodyReadError> {
+        let codings = match self.headers.get("Transfer-Encoding") {
+            Some(values) => transfer_encoding::codings(values),
+            None => {
+                return Ok(());
+            }
+        };
+
+        // Body can't be read without knowing its length, so the connection can't be reused
+        if let Err(error) = transfer_encoding::validate_codings(&codings) {
+            self.context.dont_wait();
+            return Err(error);
+        }
+
+        let max_size = self.max_body_size.unwrap_or(512 * 1024 * 1024); // 512 MiB
+        let partial_bytes = self.partial_body.take().unwrap_or_default();
+
+        let (mut body_bytes, next_request_bytes) = match transfer_encoding::decode_chunked(
+            partial_bytes, &mut self.stream.as_ref(), max_size) {
+            Ok(result) => result,
+            Err(error) => {
+                self.context.dont_wait();
+                return Err(error);
+            }
+        };
+
+        // Bytes after the chunked body belong to the next pipelined request
+        self.context.set_pending_bytes(next_request_bytes);
+
+        let decode_limits = &self.context.config.decode_limits;
+        let max_decoded_size = decode_limits.max_size.unwrap_or(max_size);
+        for coding in codings.iter().rev().skip(1) {
+            body_bytes = content_encoding::decode(coding, &body_bytes, max_decoded_size, decode_limits.max_ratio)
+                .map_err(BodyReadError::Decode)?;
+        }
+
+        self.headers.remove("Transfer-Encoding");
+        self.headers.insert("Content-Length".to_string(), vec![body_bytes.len().to_string()]);
+        self.partial_body = Some(body_bytes);
+        return Ok(());
+    }
+
+    /// Decodes the body sent with `gzip` or `deflate` `Content-Encoding`, so that the parsers
+    /// receive the decoded body. Decoded body is kept in memory and `Content-Length` is updated.
+    /// Body is left as it is if `compression` feature is disabled.
+    fn decode_content_encoding(&mut self) -> Result<(), BodyReadError> {
+        if !cfg!(feature = "compression") {
+            return Ok(());
+        }
+
+        let content_encoding = match self.headers.get("Content-Encoding").and_then(|values| values.first()) {
+            Some(value) if content_encoding::is_encoded(value) => value.clone(),
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let max_size = self.max_body_size.unwrap_or(512 * 1024 * 1024); // 512 MiB
+        let body_bytes = self.read_full_body(max_size)?;
+
+        let decode_limits = &self.context.config.decode_limits;
+        let max_decoded_size = decode_limits.max_size.unwrap_or(max_size);
+        let decoded = content_encoding::decode(&content_encoding, &body_bytes, max_decoded_size,
+                                               decode_limits.max_ratio)
+            .map_err(BodyReadError::Decode)?;
+
+        self.headers.remove("Content-Encoding");
+        self.headers.insert("Content-Length".to_string(), vec![decoded.len().to_string()]);
+        self.partial_body = Some(decoded);
+        return Ok(());
+    }
+
+    /// Returns the raw body retained by `retain_raw_body`
+    pub fn raw_body(&self) -> Option<&[u8]> {
+        return self.raw_body.as_deref();
+    }
+
+    /// Returns true if the body is already consumed by `body`, `form_data`, `files` or
+    /// `form_data_bytes`. The body can be read only once, except the body retained by
+    /// `retain_raw_body`.
+    pub fn is_body_read(&self) -> bool {
+        return self.body_read.load(Ordering::Relaxed);
+    }
+
+    /// Same as `try_body` but logs the error and returns `None`
     pub fn body(&mut self) -> Option<NamedTempFile> {
+        match self.try_body() {
+            Ok(temp_file) => {
+                return Some(temp_file);
+            }
+
+            Err(error) => {
+                eprintln!("Error: {:?}", error);
+                return None;
+            }
+        }
+    }
+
+    /// Returns the form data parsed into the nested values. See `form::form_tree`.
+    pub fn form_tree(&mut self) -> FormTree {
+        return form::form_tree(self.form_data());
+    }
+
+    /// Returns the error of parsing the body by `form_data`, `form_data_bytes` or `files`.
+    /// Empty form data is returned by these methods even if the body is invalid.
+    pub fn body_error(&self) -> Option<&RequestError> {
+        return self.body_error.as_ref();
+    }
+
+    /// Reads the body and yields its lines without the line endings. Useful for the newline
+    /// delimited formats such as NDJSON. Body read error is yielded as the only item.
+    pub fn body_lines(&mut self) -> impl Iterator<Item = io::Result<String>> {
+        let lines: Box<dyn Iterator<Item = io::Result<String>>> = match self.try_body() {
+            Ok(temp_file) => Box::new(BufReader::new(temp_file).lines()),
+            Err(error) => Box::new(std::iter::once(Err(io::Error::new(ErrorKind::InvalidData, error)))),
+        };
+
+        return lines;
+    }
+
+    /// Sets the callback which is called with the number of body bytes read and the
+    /// Content-Length while `body`, `form_data` or `files` reads the body. Useful for tracking
+    /// the upload progress. Set it before accessing the body.
+    pub fn on_body_progress<F: Fn(usize, usize) + Send + Sync + 'static>(&mut self, callback: F) {
+        self.body_progress = Some(Arc::new(callback));
+    }
+
+    /// Cancels the body read of `body`, `form_data` or `files` once the flag is set to `true`,
+    /// for example from another thread or the `on_body_progress` callback. The flag is checked
+    /// between chunks and the parsers return the `Cancelled` error. The connection is closed
+    /// after the response since the rest of the body is not read.
+    pub fn set_body_cancel_flag(&mut self, cancel: Arc<AtomicBool>) {
+        self.body_cancel = Some(cancel);
+    }
+
+    /// Wraps the body reader to report the progress and observe the cancel flag. Bytes already
+    /// read with the headers are reported first.
+    fn progress_reader<R: StreamReader>(&self, reader: R, bytes_read: usize, content_length: Option<usize>)
+                                        -> ProgressReader<R> {
+        let content_length = match content_length {
+            Some(content_length) => content_length,
+            None => {
+                // Progress can't be reported without knowing the body size
+                return ProgressReader::new(reader, bytes_read, 0, None)
+                    .with_cancel_flag(self.body_cancel.clone());
+            }
+        };
+
+        if let Some(progress) = &self.body_progress {
+            if bytes_read > 0 {
+                progress(bytes_read, content_length);
+            }
+        }
+
+        return ProgressReader::new(reader, bytes_read, content_length, self.body_progress.clone())
+            .with_cancel_flag(self.body_cancel.clone());
+    }
+
+    /// Hands over the body bytes already read with the headers and the stream to read the rest of
+    /// the body manually. Body is marked as read and the connection is closed after the response,
+    /// since the server doesn't know how much of the body is consumed.
+    pub fn take_body_stream(&mut self) -> Result<(Vec<u8>, TcpStream), BodyReadError> {
         if self.body_read.load(Ordering::Relaxed) {
-            eprintln!("Body already read");
-            return None;
+            return Err(BodyReadError::BodyAlreadyRead);
         }
 
-        let content_length = headers::content_length(&self.headers);
+        let cloned_stream = match self.stream.try_clone() {
+            Ok(stream) => stream,
+            Err(_) => {
+                return Err(BodyReadError::Others("Failed to clone stream"));
+            }
+        };
 
-        if !content_length.is_some() {
-            eprintln!("Content-Length header is missing");
-            return None;
+        let partial_bytes = self.partial_body.take().unwrap_or_default();
+        self.body_read.store(true, Ordering::Relaxed);
+        self.context.accept_next.store(false, Ordering::Relaxed);
+        return Ok((partial_bytes, cloned_stream));
+    }
+
+    /// Reads the body to a temporary file. Returns `BodyReadError::BodyAlreadyRead` if the body
+    /// is already consumed by this or any other body accessor.
+    pub fn try_body(&mut self) -> Result<NamedTempFile, BodyReadError> {
+        if self.body_read.load(Ordering::Relaxed) {
+            return Err(BodyReadError::BodyAlreadyRead);
         }
 
-        let cloned_stream = self.stream.try_clone();
-        if !cloned_stream.is_ok() {
-            eprintln!("Failed to clone stream");
-            return None;
+        if let Err(error) = self.decode_transfer_encoding().and_then(|_| self.decode_content_encoding()) {
+            self.body_read.store(true, Ordering::Relaxed);
+            return Err(error);
         }
 
+        let content_length = match headers::content_length(&self.headers) {
+            Some(content_length) => content_length,
+            None => {
+                return Err(BodyReadError::ContentLengthMissing);
+            }
+        };
+
         let limits = Limits {
-            max_body_size: 512 * 1024 * 1024, // 512 MiB
+            max_body_size: self.max_body_size.unwrap_or(512 * 1024 * 1024), // 512 MiB
         };
 
+        if content_length > limits.max_body_size {
+            return Err(BodyReadError::MaxBodySizeExceed);
+        }
+
         let mut partial_bytes: Vec<u8> = Vec::new();
         if let Some(partial) = self.partial_body.as_mut() {
             partial_bytes.extend(partial.clone());
             partial.clear();
         }
 
-        let reader = BodyReader::new(cloned_stream.unwrap(), content_length.unwrap(),
-                                     partial_bytes.len(), limits);
+        let reader = BodyReader::new(self.stream.clone(), content_length, partial_bytes.len(), limits);
+        let reader = self.progress_reader(reader, partial_bytes.len(), Some(content_length));
 
         let parse_result = body::parse(
             partial_bytes,
@@ -199,18 +1023,9 @@ impl Request {
 
         self.body_read.store(true, Ordering::Relaxed);
 
-        match parse_result {
-            Ok(temp_file) => {
-                self.body_parsed.store(true, Ordering::Relaxed);
-                return Some(temp_file);
-            }
-
-            Err(error) => {
-                eprintln!("Error: {:?}", error);
-            }
-        }
-
-        return None;
+        let temp_file = parse_result?;
+        self.body_parsed.store(true, Ordering::Relaxed);
+        return Ok(temp_file);
     }
 
     pub fn form_data(&mut self) -> &mut FormData {
@@ -220,6 +1035,26 @@ impl Request {
         return &mut self.form_data;
     }
 
+    /// Returns the form field values without converting to string. `form_data` replaces invalid
+    /// UTF-8 sequences of multipart values, so use it for the fields with binary values.
+    pub fn form_data_bytes(&mut self) -> &mut FormDataBytes {
+        if !self.body_read.load(Ordering::Relaxed) {
+            self.parse_request_body();
+        }
+        return &mut self.form_data_bytes;
+    }
+
+    /// Returns the query parameter value according to `duplicate_policy`
+    pub fn query_value(&self, name: &str) -> Result<Option<String>, FormError> {
+        return self.query_params.value_with_policy(name, self.duplicate_policy);
+    }
+
+    /// Returns the form field value according to `duplicate_policy`
+    pub fn form_value(&mut self, name: &str) -> Result<Option<String>, FormError> {
+        let policy = self.duplicate_policy;
+        return self.form_data().value_with_policy(name, policy);
+    }
+
     pub fn files(&mut self) -> &mut FormFiles {
         if !self.body_read.load(Ordering::Relaxed) {
             self.parse_request_body();
@@ -229,6 +1064,13 @@ impl Request {
     }
 
     pub fn parse_request_body(&mut self) {
+        if let Err(error) = self.decode_transfer_encoding().and_then(|_| self.decode_content_encoding()) {
+            self.body_read.store(true, Ordering::Relaxed);
+            eprintln!("Error: {:?}", error);
+            self.body_error = Some(error.into());
+            return;
+        }
+
         let content_type = headers::extract_content_type(&self.headers);
 
         // Silently return success empty form data if it does not have body
@@ -238,6 +1080,7 @@ impl Request {
 
             if content_length.is_some() && content_length.unwrap() > 0 {
                 eprintln!("Body has content, but missing content type.");
+                self.body_error = Some(RequestError::MissingContentType);
             }
 
             return;
@@ -253,7 +1096,7 @@ impl Request {
             const MAX_VALUE_SIZE: usize = 2 * 1024; // 1 MiB
 
             let limits = multipart::Limits {
-                max_body_size: Some(MAX_BODY_SIZE),
+                max_body_size: Some(self.max_body_size.unwrap_or(MAX_BODY_SIZE)),
                 max_header_size: Some(MAX_HEADER_SIZE),
                 max_value_size: Some(MAX_VALUE_SIZE),
                 form_part_limits: HashMap::new(),
@@ -270,6 +1113,7 @@ impl Request {
 
             match result {
                 Ok(form_parts) => {
+                    self.form_data_bytes = self.multipart_form_data_bytes(&form_parts);
                     let (form_data, form_files) = self.multipart_form_data_and_files(form_parts);
                     // Set body parsed to true
                     self.body_parsed.store(true, Ordering::Relaxed);
@@ -280,16 +1124,18 @@ impl Request {
                 Err(error) => {
                     self.body_parsed.store(true, Ordering::Relaxed);
                     eprintln!("Error: {:?}", error);
+                    self.body_error = Some(error.into());
                 }
             }
         } else if content_type_value.starts_with("application/x-www-form-urlencoded") {
             let limits = url_encoded::Limits {
-                max_body_size: 2 * 1024 * 1024 // 2 MiB
+                max_body_size: self.max_body_size.unwrap_or(2 * 1024 * 1024) // 2 MiB
             };
 
             if !content_length.is_some() {
                 // Content-Length header is required for "application/x-www-form-urlencoded"
                 eprintln!("Content-Length is missing.");
+                self.body_error = Some(BodyReadError::ContentLengthMissing.into());
                 return;
             }
 
@@ -302,13 +1148,25 @@ impl Request {
             match result {
                 Ok(form_fields) => {
                     self.body_parsed.store(true, Ordering::Relaxed);
+
+                    // Url encoded values are already decoded as string
+                    for (name, values) in &form_fields {
+                        let values = values.iter().map(|value| value.as_bytes().to_vec()).collect();
+                        self.form_data_bytes.insert(name.clone(), values);
+                    }
+
                     self.form_data = form_fields;
                 }
 
                 Err(error) => {
                     eprintln!("Error: {:?}", error);
+                    self.body_error = Some(error.into());
                 }
             }
+        } else {
+            // Body is kept unread, so that the view can still read it using `body`
+            let media_type = content_type_value.split(';').next().unwrap_or("").trim();
+            self.body_error = Some(RequestError::UnsupportedContentType(media_type.to_string()));
         }
     }
 
@@ -328,27 +1186,21 @@ impl Request {
             partial_body = Vec::new();
         }
 
-        return match self.stream.try_clone() {
-            Ok(cloned_stream) => {
-                // This will work as source of data
-                let reader = FormDataReader::new(
-                    cloned_stream,
-                    boundary.unwrap(),
-                    content_length,
-                    partial_body.len(),
-                );
+        // This will work as source of data
+        let reader = FormDataReader::new(
+            self.stream.clone(),
+            boundary.unwrap(),
+            content_length,
+            partial_body.len(),
+        );
+        let reader = self.progress_reader(reader, partial_body.len(), content_length);
 
-                multipart::parse(
-                    partial_body,
-                    &self.headers,
-                    reader,
-                    limits,
-                )
-            }
-            Err(_) => {
-                Err(MultipartFormDataError::Others("Failed to copy stream"))
-            }
-        };
+        return multipart::parse(
+            partial_body,
+            &self.headers,
+            reader,
+            limits,
+        );
     }
 
     pub fn parse_url_encoded(&mut self, content_length: usize, limits: url_encoded::Limits)
@@ -360,16 +1212,29 @@ impl Request {
             partial_body.clear();
         }
 
-        let cloned_stream = self.stream.try_clone().expect("Failed to clone stream");
-        let mut reader = UrlEncodedReader::new(
-            cloned_stream,
+        let reader = UrlEncodedReader::new(
+            self.stream.clone(),
             content_length,
             partial_bytes.len(),
         );
+        let mut reader = self.progress_reader(reader, partial_bytes.len(), Some(content_length));
 
         return url_encoded::parse(partial_bytes, &self.headers, &mut reader, limits);
     }
 
+    /// Returns the raw bytes of the multipart field values
+    pub fn multipart_form_data_bytes(&self, form_parts: &[FormPart]) -> FormDataBytes {
+        let mut form_data_bytes = FormDataBytes::new();
+
+        for form_part in form_parts {
+            if let (Some(name), Some(value)) = (&form_part.name, &form_part.value) {
+                form_data_bytes.entry(name.clone()).or_default().push(value.clone());
+            }
+        }
+
+        return form_data_bytes;
+    }
+
     pub fn multipart_form_data_and_files(&self, form_parts: Vec<FormPart>) -> (FormData, FormFiles) {
         let mut form_data = FormData::new();
         let mut form_files = FormFiles::new();
@@ -408,6 +1273,7 @@ impl Request {
                 let temp_file = temp_file.expect("Error in parsing file body. At least expected one temp file.");
                 let form_file = FormFile {
                     filename,
+                    content_type: form_part.content_type,
                     temp_file,
                 };
 
@@ -419,24 +1285,755 @@ impl Request {
     }
 }
 
+impl Request {
+    /// Same as `clone`. It doesn't fail since the stream is shared instead of duplicated.
+    #[deprecated(note = "Use `clone`. The stream is shared, so cloning can't fail.")]
+    pub fn try_clone_request(&self) -> io::Result<Request> {
+        return Ok(self.clone());
+    }
+}
+
 impl Clone for Request {
+    /// Stream is shared with the clone, so no new file descriptor is used. Form data and files
+    /// are not copied.
     fn clone(&self) -> Self {
         return Request {
             context: self.context.clone(),
-            stream: self.stream.try_clone().unwrap(),
+            stream: self.stream.clone(),
             method: self.method.clone(),
+            version: self.version.clone(),
+            request_id: self.request_id.clone(),
             raw_path: self.raw_path.clone(),
             pathname: self.pathname.clone(),
             query_params: self.query_params.clone(),
+            path_params: self.path_params.clone(),
+            matched_route: self.matched_route.clone(),
             headers: self.headers.clone(),
+            max_body_size: self.max_body_size,
+            duplicate_policy: self.duplicate_policy,
             partial_body: self.partial_body.clone(),
+            raw_body: self.raw_body.clone(),
             // We are not copying value field and files
             form_data: FormData::new(),
+            form_data_bytes: FormDataBytes::new(),
             form_files: FormFiles::new(),
+            body_error: None,
+            raw_headers: self.raw_headers.clone(),
+            body_progress: self.body_progress.clone(),
+            body_cancel: self.body_cancel.clone(),
             body_read: self.body_read.clone(),
             body_parsed: self.body_parsed.clone(),
+            sequence: self.sequence,
         };
     }
 }
 
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use crate::headers::Headers;
+    use crate::parser::body::BodyReadError;
+    use crate::parser::multipart::MultipartFormDataError;
+    use crate::request::form::{DuplicatePolicy, FormData, FormError, FormFileMethods, FormValue, MapFirstStringMethod,
+                               form_tree, validate_required};
+    use crate::request::{Request, RequestError};
+    use crate::server::{Config, Context};
+
+    /// Returns request of connected local stream and the client stream
+    fn connected_request(method: &str, raw_path: &str, headers: Headers) -> (Request, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let context = Arc::new(Context::new(Arc::new(Config::default())));
+        let mut request = Request::new(context, stream, method.to_string(), raw_path.to_string(), headers,
+                                       Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+        request.setup();
+        return (request, client);
+    }
+
+    /// Recorded `(bytes_read, content_length)` of the body progress callback
+    type Progress = Arc<Mutex<Vec<(usize, usize)>>>;
+
+    /// Returns the request which records the body progress
+    fn progress_request(headers: Headers) -> (Request, TcpStream, Progress) {
+        let (mut request, client) = connected_request("POST", "/", headers);
+        let progress = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = progress.clone();
+        request.on_body_progress(move |bytes_read, content_length| {
+            recorded.lock().unwrap().push((bytes_read, content_length));
+        });
+
+        return (request, client, progress);
+    }
+
+    #[test]
+    fn test_body_progress() {
+        let body = "a".repeat(5000);
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, mut client, progress) = progress_request(headers);
+        request.set_partial_body_bytes(body.as_bytes()[..100].to_vec());
+        client.write_all(&body.as_bytes()[100..]).unwrap();
+        request.try_body().unwrap();
+
+        let progress = progress.lock().unwrap();
+        assert_eq!(true, progress.len() > 1);
+        assert_eq!((100, 5000), progress[0]);
+        assert_eq!(true, progress.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(true, progress.iter().all(|(_, content_length)| *content_length == 5000));
+        assert_eq!(Some(&(5000, 5000)), progress.last());
+
+        let body = format!("--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n{}\r\n--XYZ--\r\n", body);
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, mut client, progress) = progress_request(headers);
+        client.write_all(body.as_bytes()).unwrap();
+        assert_eq!(1, request.files().len());
+        assert_eq!(Some(&(body.len(), body.len())), progress.lock().unwrap().last());
+    }
+
+    #[test]
+    fn test_body_cancel_flag() {
+        let body = "a".repeat(32 * 1024);
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/", headers);
+        let cancel = Arc::new(AtomicBool::new(false));
+        request.set_body_cancel_flag(cancel.clone());
+        request.on_body_progress(move |bytes_read, _| {
+            if bytes_read > 1000 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+
+        client.write_all(body.as_bytes()).unwrap();
+        assert_eq!(true, matches!(request.try_body(), Err(BodyReadError::Cancelled)));
+        assert_eq!(false, request.body_parsed.load(Ordering::Relaxed));
+
+        let body = format!("--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n{}\r\n--XYZ--\r\n", body);
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/", headers);
+        request.set_body_cancel_flag(Arc::new(AtomicBool::new(true)));
+        client.write_all(body.as_bytes()).unwrap();
+
+        assert_eq!(0, request.files().len());
+        let error = request.body_error().unwrap();
+        assert_eq!(true, matches!(error, RequestError::Multipart(MultipartFormDataError::Cancelled)));
+    }
+
+    #[test]
+    fn test_clone_request() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("X-Tag".to_string(), vec!["a".to_string()]);
+
+        let (request, mut client) = connected_request("PUT", "/users?id=42", headers);
+        let cloned = request.clone();
+        assert_eq!("PUT", cloned.method);
+        assert_eq!("/users", cloned.pathname);
+        assert_eq!(request.request_id, cloned.request_id);
+        assert_eq!(vec!["a"], cloned.headers["X-Tag"]);
+
+        // Clone shares the connection and the body state
+        cloned.body_read.store(true, Ordering::Relaxed);
+        assert_eq!(true, request.body_read.load(Ordering::Relaxed));
+
+        cloned.stream.as_ref().write_all(b"ping").unwrap();
+        let mut received = [0u8; 4];
+        client.read_exact(&mut received).unwrap();
+        assert_eq!(b"ping", &received);
+    }
+
+    #[test]
+    fn test_take_body_stream() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Length".to_string(), vec!["11".to_string()]);
+        headers.insert("Connection".to_string(), vec!["keep-alive".to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(b"hello".to_vec());
+        client.write_all(b" world").unwrap();
+
+        let (partial_bytes, mut stream) = request.take_body_stream().unwrap();
+        let mut rest = vec![0u8; 11 - partial_bytes.len()];
+        stream.read_exact(&mut rest).unwrap();
+        assert_eq!(b"hello world", [partial_bytes, rest].concat().as_slice());
+
+        assert_eq!(true, matches!(request.take_body_stream(), Err(BodyReadError::BodyAlreadyRead)));
+        assert_eq!(true, matches!(request.try_body(), Err(BodyReadError::BodyAlreadyRead)));
+        assert_eq!(true, request.should_close_connection());
+    }
+
+    #[test]
+    fn test_content_type_params() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["text/plain; Charset=\"UTF-8\"; format=flowed".to_string()]);
+        let (request, _client) = connected_request("GET", "/", headers);
+
+        let params = request.content_type_params();
+        assert_eq!(2, params.len());
+        assert_eq!("UTF-8", params["charset"]);
+        assert_eq!("flowed", params["format"]);
+
+        let (request, _client) = connected_request("GET", "/", HashMap::new());
+        assert_eq!(true, request.content_type_params().is_empty());
+    }
+
+    #[test]
+    fn test_raw_query() {
+        let raw_query = "amount=10%2E00&sig=a%2Bb%3D&name=John+Doe";
+        let (request, _client) = connected_request("GET", &format!("/callback?{}", raw_query), HashMap::new());
+        assert_eq!(Some(raw_query), request.raw_query());
+        assert_eq!("/callback", request.pathname);
+
+        let (request, _client) = connected_request("GET", "/callback", HashMap::new());
+        assert_eq!(None, request.raw_query());
+    }
+
+    #[test]
+    fn test_raw_body_after_parsing() {
+        let body = "name=John%20Doe&age=22";
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["application/x-www-form-urlencoded".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/webhook", headers);
+        client.write_all(body.as_bytes()).unwrap();
+
+        assert_eq!(true, request.retain_raw_body(1024).is_ok());
+        assert_eq!(Some(body.as_bytes()), request.raw_body());
+
+        let form_data = request.form_data();
+        assert_eq!(Some("John Doe".to_string()), form_data.value("name"));
+        assert_eq!(Some(body.as_bytes()), request.raw_body());
+    }
+
+    #[test]
+    fn test_post_without_body() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["keep-alive".to_string()]);
 
+        let (request, _client) = connected_request("POST", "/", headers.clone());
+        assert_eq!(false, request.has_body());
+        assert_eq!(true, request.body_read.load(Ordering::Relaxed));
+        assert_eq!(false, request.should_close_connection());
+
+        headers.insert("Content-Length".to_string(), vec!["10".to_string()]);
+        let (request, _client) = connected_request("POST", "/", headers);
+        assert_eq!(true, request.has_body());
+        assert_eq!(false, request.body_read.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_single_value() {
+        let mut form_data = FormData::new();
+        form_data.insert("name".to_string(), vec!["John".to_string()]);
+        form_data.insert("role".to_string(), vec!["user".to_string(), "admin".to_string()]);
+
+        assert_eq!(Ok("John".to_string()), form_data.single("name"));
+        assert_eq!(Err(FormError::Missing("age".to_string())), form_data.single("age"));
+        assert_eq!(Err(FormError::MultipleValues("role".to_string())), form_data.single("role"));
+    }
+
+    #[test]
+    fn test_duplicate_policy() {
+        let (mut request, _client) = connected_request("GET", "/?id=1&id=2&name=John", HashMap::new());
+
+        assert_eq!(Ok(Some("1".to_string())), request.query_value("id"));
+
+        request.duplicate_policy = DuplicatePolicy::Last;
+        assert_eq!(Ok(Some("2".to_string())), request.query_value("id"));
+
+        request.duplicate_policy = DuplicatePolicy::Reject;
+        assert_eq!(Err(FormError::MultipleValues("id".to_string())), request.query_value("id"));
+        assert_eq!(Ok(Some("John".to_string())), request.query_value("name"));
+        assert_eq!(Ok(None), request.query_value("age"));
+    }
+
+    #[test]
+    fn test_form_duplicate_policy() {
+        let body = "id=1&id=2";
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["application/x-www-form-urlencoded".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        // Body is received along with the headers
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(body.as_bytes().to_vec());
+
+        request.duplicate_policy = DuplicatePolicy::Reject;
+        assert_eq!(Err(FormError::MultipleValues("id".to_string())), request.form_value("id"));
+
+        request.duplicate_policy = DuplicatePolicy::Last;
+        assert_eq!(Ok(Some("2".to_string())), request.form_value("id"));
+    }
+
+    #[test]
+    fn test_form_data_bytes() {
+        let mut body = b"--XYZ\r\nContent-Disposition: form-data; name=\"data\"\r\n\r\n".to_vec();
+        body.extend([0xff, 0xfe, 0x00, b'a']);
+        body.extend(b"\r\n--XYZ--\r\n");
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(body);
+
+        let values = request.form_data_bytes().get("data").cloned();
+        assert_eq!(Some(vec![vec![0xff, 0xfe, 0x00, b'a']]), values);
+
+        // String value is still available with replaced characters
+        assert_eq!(Some("\u{FFFD}\u{FFFD}\u{0}a".to_string()), request.form_data().value("data"));
+    }
+
+    #[test]
+    fn test_body_after_form_data() {
+        let body = "name=John";
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["application/x-www-form-urlencoded".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/", headers);
+        client.write_all(body.as_bytes()).unwrap();
+
+        assert_eq!(false, request.is_body_read());
+        assert_eq!(Some("John".to_string()), request.form_data().value("name"));
+        assert_eq!(true, request.is_body_read());
+
+        assert_eq!(true, matches!(request.try_body(), Err(BodyReadError::BodyAlreadyRead)));
+        assert_eq!(true, request.body().is_none());
+
+        // Parsed form data is still available
+        assert_eq!(Some("John".to_string()), request.form_data().value("name"));
+    }
+
+    #[test]
+    fn test_save_uploaded_file() {
+        let body = "--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+                    Content-Type: text/plain\r\n\r\nhello\r\n--XYZ--\r\n";
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(body.as_bytes().to_vec());
+
+        let form_file = request.files().remove("file").unwrap().remove(0);
+        assert_eq!("a.txt", form_file.filename);
+
+        let directory = tempfile::tempdir().unwrap();
+        let saved_path = directory.path().join("saved.txt");
+        assert_eq!(5, form_file.save_to(&saved_path).unwrap());
+        assert_eq!("hello", fs::read_to_string(&saved_path).unwrap());
+
+        let persisted_path = directory.path().join("persisted.txt");
+        let temp_path = form_file.temp_file.path().to_path_buf();
+        form_file.persist(&persisted_path).unwrap();
+        assert_eq!("hello", fs::read_to_string(&persisted_path).unwrap());
+        assert_eq!(false, temp_path.exists());
+    }
+
+    #[test]
+    fn test_validate_required() {
+        let body = "--XYZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHoliday\r\n\
+                    --XYZ\r\nContent-Disposition: form-data; name=\"description\"\r\n\r\n\r\n\
+                    --XYZ\r\nContent-Disposition: form-data; name=\"image\"; filename=\"a.png\"\r\n\
+                    Content-Type: image/png\r\n\r\nimage\r\n--XYZ--\r\n";
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(body.as_bytes().to_vec());
+        let form_data = request.form_data().clone();
+        let form_files = request.files();
+
+        let report = validate_required(&form_data, form_files, &["title", "image"]);
+        assert_eq!(true, report.is_valid());
+
+        // Empty value is treated as missing
+        let report = validate_required(&form_data, form_files, &["title", "description", "image", "tags"]);
+        assert_eq!(false, report.is_valid());
+        assert_eq!(vec!["description", "tags"], report.missing);
+        assert_eq!(FormError::Missing("tags".to_string()), report.errors().remove(1));
+    }
+
+    /// Returns true if any temporary file contains the marker
+    fn temp_file_contains(marker: &str) -> bool {
+        for entry in fs::read_dir(std::env::temp_dir()).unwrap().flatten() {
+            if !entry.file_name().to_string_lossy().starts_with(".tmp") {
+                continue;
+            }
+
+            if let Ok(content) = fs::read(entry.path()) {
+                if content.windows(marker.len()).any(|window| window == marker.as_bytes()) {
+                    return true;
+                }
+            }
+        }
+
+        return false;
+    }
+
+    #[test]
+    fn test_multipart_client_disconnected() {
+        let marker = format!("disconnect-marker-{}", std::process::id());
+        let body = format!("--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+                            Content-Type: text/plain\r\n\r\n{}", marker.repeat(1024));
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![(body.len() * 2).to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/", headers);
+        client.write_all(body.as_bytes()).unwrap();
+        drop(client);
+
+        assert_eq!(0, request.files().len());
+        let error = request.body_error().unwrap();
+        assert_eq!(true, matches!(error, RequestError::Multipart(MultipartFormDataError::ClientDisconnected)));
+        assert_eq!(false, temp_file_contains(&marker));
+    }
+
+    #[test]
+    fn test_empty_file_upload() {
+        let body = "--XYZ\r\nContent-Disposition: form-data; name=\"empty\"; filename=\"empty.txt\"\r\n\
+                    Content-Type: text/plain\r\n\r\n\r\n--XYZ\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\n\
+                    John\r\n--XYZ\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\n\r\n\
+                    --XYZ\r\nContent-Disposition: form-data; name=\"last\"; filename=\"last.txt\"\r\n\r\n\
+                    \r\n--XYZ--\r\n";
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(body.as_bytes().to_vec());
+
+        let empty = request.files().value("empty").unwrap();
+        assert_eq!("empty.txt", empty.filename);
+        assert_eq!(0, empty.temp_file.as_file().metadata().unwrap().len());
+        assert_eq!(Some("John".to_string()), request.form_data().value("name"));
+        assert_eq!(Some("".to_string()), request.form_data().value("note"));
+        let last = request.files().value("last").unwrap();
+        assert_eq!(0, last.temp_file.as_file().metadata().unwrap().len());
+    }
+
+    #[test]
+    fn test_sniff_mime() {
+        let mut body = b"--XYZ\r\nContent-Disposition: form-data; name=\"image\"; filename=\"a.pdf\"\r\n\
+                         Content-Type: application/pdf\r\n\r\n".to_vec();
+        body.extend(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR");
+        body.extend(b"\r\n--XYZ\r\nContent-Disposition: form-data; name=\"text\"; filename=\"a.txt\"\r\n\r\nhello\r\n--XYZ--\r\n");
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["multipart/form-data; boundary=XYZ".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(body);
+
+        let image = request.files().value("image").unwrap();
+        assert_eq!(Some("application/pdf".to_string()), image.content_type);
+        assert_eq!(Some("image/png"), image.sniff_mime());
+
+        // Spoofed content type is detected
+        assert_eq!(false, image.content_type.as_deref() == image.sniff_mime());
+
+        let text = request.files().value("text").unwrap();
+        assert_eq!(None, text.content_type);
+        assert_eq!(None, text.sniff_mime());
+    }
+
+    #[test]
+    fn test_request_error() {
+        use std::error::Error;
+        use tempfile::NamedTempFile;
+
+        let error = RequestError::from(BodyReadError::BodyAlreadyRead);
+        assert_eq!("Body is already read", error.to_string());
+        assert_eq!("Body is already read", error.source().unwrap().to_string());
+
+        let error: RequestError = MultipartFormDataError::MaxFieldSizeExceed("avatar".to_string(), "Field too large").into();
+        assert_eq!("avatar: Field too large", error.to_string());
+
+        let error: Box<dyn Error> = Box::new(FormError::MultipleValues("id".to_string()));
+        assert_eq!("Field id has multiple values", error.to_string());
+
+        // Works with the errors of other libraries
+        fn read_body(request: &mut Request) -> Result<NamedTempFile, Box<dyn Error>> {
+            let temp_file = request.try_body().map_err(RequestError::from)?;
+            return Ok(temp_file);
+        }
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Length".to_string(), vec!["0".to_string()]);
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.body_read.store(true, Ordering::Relaxed);
+        assert_eq!("Body is already read", read_body(&mut request).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_content_length_and_type() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["application/json".to_string()]);
+        headers.insert("Content-Length".to_string(), vec!["25".to_string()]);
+
+        let (request, _client) = connected_request("POST", "/", headers);
+        assert_eq!(Some(25), request.content_length());
+        assert_eq!(Some("application/json"), request.content_type());
+
+        let (request, _client) = connected_request("GET", "/", HashMap::new());
+        assert_eq!(None, request.content_length());
+        assert_eq!(None, request.content_type());
+    }
+
+    #[cfg(feature = "compression")]
+    fn compressed_request(content_encoding: &str, content_type: &str, body: &[u8]) -> (Request, TcpStream) {
+        use flate2::Compression;
+        use flate2::write::{GzEncoder, ZlibEncoder};
+
+        let compressed = if content_encoding == "gzip" {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        } else {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec![content_type.to_string()]);
+        headers.insert("Content-Encoding".to_string(), vec![content_encoding.to_string()]);
+        headers.insert("Content-Length".to_string(), vec![compressed.len().to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/", headers);
+        request.setup();
+        client.write_all(&compressed).unwrap();
+        return (request, client);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_gzip_body() {
+        let json = r#"{"name": "John", "age": 22}"#;
+        let (mut request, _client) = compressed_request("gzip", "application/json", json.as_bytes());
+
+        let body = request.body().unwrap();
+        assert_eq!(json, std::fs::read_to_string(body.path()).unwrap());
+        assert_eq!(Some(json.len()), request.content_length());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_deflate_form_data() {
+        let form = "name=John&age=22";
+        let (mut request, _client) = compressed_request("deflate", "application/x-www-form-urlencoded",
+                                                        form.as_bytes());
+
+        assert_eq!(Ok(Some("John".to_string())), request.form_value("name"));
+        assert_eq!(Ok(Some("22".to_string())), request.form_value("age"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_decompression_bomb() {
+        use crate::parser::body::BodyReadError;
+        use crate::parser::content_encoding::DecodeError;
+
+        let payload = vec![b'a'; 4 * 1024 * 1024];
+        let (mut request, _client) = compressed_request("gzip", "text/plain", &payload);
+        request.max_body_size = Some(1024 * 1024);
+
+        let result = request.decode_content_encoding();
+        assert_eq!(true, matches!(result, Err(BodyReadError::Decode(DecodeError::MaxSizeExceed))));
+    }
+
+    fn chunked_request(transfer_encoding: &str, content_type: &str) -> (Request, TcpStream) {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec![content_type.to_string()]);
+        headers.insert("Transfer-Encoding".to_string(), vec![transfer_encoding.to_string()]);
+        return connected_request("POST", "/", headers);
+    }
+
+    #[test]
+    fn test_chunked_form_data() {
+        let (mut request, mut client) = chunked_request("chunked", "application/x-www-form-urlencoded");
+        client.write_all(b"9\r\nname=John\r\n7\r\n&age=22\r\n0\r\n\r\n").unwrap();
+
+        assert_eq!(Ok(Some("John".to_string())), request.form_value("name"));
+        assert_eq!(Ok(Some("22".to_string())), request.form_value("age"));
+    }
+
+    #[test]
+    fn test_missing_content_type() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Length".to_string(), vec!["9".to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(b"name=John".to_vec());
+
+        assert_eq!(0, request.form_data().len());
+        assert_eq!(true, matches!(request.body_error(), Some(RequestError::MissingContentType)));
+
+        // Request without body is not an error
+        let (mut request, _client) = connected_request("GET", "/", HashMap::new());
+        assert_eq!(0, request.form_data().len());
+        assert_eq!(true, request.body_error().is_none());
+    }
+
+    #[test]
+    fn test_json_form_data() {
+        let json = r#"{"name": "John"}"#;
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["application/json; charset=utf-8".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![json.len().to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(json.as_bytes().to_vec());
+
+        assert_eq!(0, request.form_data().len());
+        let error = request.body_error().unwrap();
+        assert_eq!(true, matches!(error, RequestError::UnsupportedContentType(content_type) if content_type == "application/json"));
+        assert_eq!("Content type application/json is not form data. Read it using body instead", error.to_string());
+
+        // Body is still available
+        let body = request.body().unwrap();
+        assert_eq!(json, fs::read_to_string(body.path()).unwrap());
+    }
+
+    #[test]
+    fn test_upgrade_protocol() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["keep-alive, Upgrade".to_string()]);
+        headers.insert("Upgrade".to_string(), vec!["websocket".to_string()]);
+        let (request, _client) = connected_request("GET", "/chat", headers);
+        assert_eq!(Some("websocket".to_string()), request.upgrade_protocol());
+
+        // Upgrade header is ignored without the connection token
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["keep-alive".to_string()]);
+        headers.insert("Upgrade".to_string(), vec!["h2c".to_string()]);
+        let (request, _client) = connected_request("GET", "/", headers);
+        assert_eq!(None, request.upgrade_protocol());
+    }
+
+    #[test]
+    fn test_form_tree() {
+        let mut form_data: FormData = HashMap::new();
+        form_data.insert("user[name]".to_string(), vec!["John".to_string()]);
+        form_data.insert("user.address.city".to_string(), vec!["Pokhara".to_string()]);
+        form_data.insert("items[0][id]".to_string(), vec!["1".to_string()]);
+        form_data.insert("items[1][id]".to_string(), vec!["2".to_string()]);
+        form_data.insert("tags[]".to_string(), vec!["a".to_string(), "b".to_string()]);
+        form_data.insert("page".to_string(), vec!["3".to_string()]);
+
+        let tree = form_tree(&form_data);
+        assert_eq!(Some("John"), tree["user"].get("name").and_then(|name| name.as_str()));
+        assert_eq!(Some("Pokhara"), tree["user"].get("address").and_then(|address| address.get("city"))
+            .and_then(|city| city.as_str()));
+        assert_eq!(Some("2"), tree["items"].get("1").and_then(|item| item.get("id")).and_then(|id| id.as_str()));
+        assert_eq!(FormValue::List(vec![FormValue::Value("a".to_string()), FormValue::Value("b".to_string())]),
+                   tree["tags"]);
+        assert_eq!(Some("3"), tree["page"].as_str());
+
+        // Malformed names are kept as they are
+        let mut form_data: FormData = HashMap::new();
+        form_data.insert("a[b".to_string(), vec!["1".to_string()]);
+        form_data.insert("[c]".to_string(), vec!["2".to_string()]);
+        let tree = form_tree(&form_data);
+        assert_eq!(Some("1"), tree["a[b"].as_str());
+        assert_eq!(Some("2"), tree["[c]"].as_str());
+    }
+
+    #[test]
+    fn test_request_form_tree() {
+        let body = "user%5Bname%5D=John&user%5Bage%5D=22";
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["application/x-www-form-urlencoded".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, _client) = connected_request("POST", "/", headers);
+        request.set_partial_body_bytes(body.as_bytes().to_vec());
+
+        let tree = request.form_tree();
+        assert_eq!(Some("22"), tree["user"].get("age").and_then(|age| age.as_str()));
+    }
+
+    #[test]
+    fn test_body_lines() {
+        let body = "{\"id\": 1}\n{\"id\": 2}\r\n{\"id\": 3}\n";
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), vec!["application/x-ndjson".to_string()]);
+        headers.insert("Content-Length".to_string(), vec![body.len().to_string()]);
+
+        let (mut request, mut client) = connected_request("POST", "/", headers);
+        client.write_all(body.as_bytes()).unwrap();
+
+        let lines: Vec<String> = request.body_lines().map(|line| line.unwrap()).collect();
+        assert_eq!(vec!["{\"id\": 1}", "{\"id\": 2}", "{\"id\": 3}"], lines);
+
+        // Body can't be read again
+        let mut lines = request.body_lines();
+        assert_eq!(true, lines.next().unwrap().is_err());
+        assert_eq!(true, lines.next().is_none());
+
+        let (mut request, mut client) = chunked_request("chunked", "application/x-ndjson");
+        client.write_all(b"b\r\n{\"id\": 1}\n{\r\n9\r\n\"id\": 2}\n\r\n0\r\n\r\n").unwrap();
+
+        let lines: Vec<String> = request.body_lines().map(|line| line.unwrap()).collect();
+        assert_eq!(vec!["{\"id\": 1}", "{\"id\": 2}"], lines);
+    }
+
+    #[test]
+    fn test_invalid_transfer_coding_order() {
+        let (mut request, _client) = chunked_request("chunked, gzip", "application/json");
+
+        let result = request.try_body();
+        assert_eq!(true, matches!(result, Err(BodyReadError::InvalidTransferEncoding(_))));
+        assert_eq!(true, request.should_close_connection());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_gzip_chunked_body() {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let json = r#"{"name": "John", "age": 22}"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let (mut request, mut client) = chunked_request("gzip, chunked", "application/json");
+        let (first, second) = compressed.split_at(10);
+        for chunk in [first, second] {
+            client.write_all(format!("{:x}\r\n", chunk.len()).as_bytes()).unwrap();
+            client.write_all(chunk).unwrap();
+            client.write_all(b"\r\n").unwrap();
+        }
+        client.write_all(b"0\r\n\r\n").unwrap();
+
+        let body = request.body().unwrap();
+        assert_eq!(json, std::fs::read_to_string(body.path()).unwrap());
+        assert_eq!(Some(json.len()), request.content_length());
+    }
+}
@@ -1,25 +1,75 @@
 use std::collections::HashMap;
-use std::io::{BufWriter, Write};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::net::{Shutdown};
-use crate::headers::Headers;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::Ordering;
+use std::time::UNIX_EPOCH;
+use tempfile::NamedTempFile;
+use crate::headers;
+use crate::headers::{ByteRange, Headers};
+use crate::parser::url_encode;
 use crate::request::Request;
-use crate::status::{Status, StatusCode, StatusMethods};
+use crate::router::join_path;
+use crate::status::{is_valid_status_code, Status, StatusCode, StatusMethods};
+
+/// File streamed as the response body
+enum BodyFile {
+    /// Deleted after the response is sent
+    Temp(NamedTempFile),
+    Regular(File),
+}
+
+/// Part of the file streamed as the response body. Length is read before sending the headers,
+/// so the `Content-Length` always matches the bytes written.
+struct FileBody {
+    file: BodyFile,
+    /// Offset of the first byte sent
+    start: u64,
+    length: u64,
+}
 
 pub struct Response {
     pub request: Request,
+    /// HTTP version of the status line. Defaults to the request version.
+    pub version: String,
     // Response headers
     pub headers: Option<Headers>,
     pub status: Option<usize>,
+    /// Reason phrase of the status line used instead of the default one. See `set_status_reason`.
+    status_reason: Option<String>,
     pub fixed_content: Option<String>,
+    /// Binary response body. It is used instead of `fixed_content` if available.
+    pub fixed_bytes: Option<Vec<u8>>,
+    /// File response body streamed without loading it to the memory. It is used instead of
+    /// `fixed_bytes` and `fixed_content` if available. See `send_temp_file`.
+    fixed_file: Option<FileBody>,
+    sent: bool,
+    /// True for the response passed to the error view, so the view can't call itself again
+    in_error_view: bool,
 }
 
 impl Response {
     pub fn new(request: Request) -> Self {
+        // Only HTTP/1.0 and HTTP/1.1 responses are supported
+        let version = match request.version.as_str() {
+            "HTTP/1.0" => "HTTP/1.0",
+            _ => "HTTP/1.1",
+        };
+
         return Self {
             request,
+            version: version.to_string(),
             headers: None,
             status: None,
+            status_reason: None,
             fixed_content: None,
+            fixed_bytes: None,
+            fixed_file: None,
+            sent: false,
+            in_error_view: false,
         };
     }
 
@@ -46,8 +96,25 @@ impl Response {
         }
     }
 
-    /// Headers will be keep appending to the list if already exists
+    /// Returns the Content-Type of the response if set
+    pub fn content_type(&self) -> Option<&str> {
+        if let Some(headers) = &self.headers {
+            if let Some(values) = headers.get("Content-Type") {
+                return values.first().map(|value| value.as_str());
+            }
+        }
+
+        return None;
+    }
+
+    /// Headers will be keep appending to the list if already exists.
+    /// Content-Type can only have one instance, so it is replaced instead.
     pub fn add_header(&mut self, name: &str, value: &str) -> &mut Self {
+        if name.eq_ignore_ascii_case("Content-Type") {
+            self.set_content_type(value);
+            return self;
+        }
+
         self.init_headers();
 
         // Unwrap header hashmap
@@ -64,6 +131,45 @@ impl Response {
         return self;
     }
 
+    /// Replaces all the existing values of the header with the given value
+    pub fn set_header(&mut self, name: &str, value: &str) -> &mut Self {
+        if name.eq_ignore_ascii_case("Content-Type") {
+            self.set_content_type(value);
+            return self;
+        }
+
+        self.init_headers();
+
+        if let Some(ref mut headers) = self.headers {
+            headers.insert(name.to_string(), vec![value.to_string()]);
+        }
+
+        return self;
+    }
+
+    /// Sets `Content-Disposition` header so that the browser downloads the response as file.
+    /// Non ASCII filenames are sent using `filename*` parameter.
+    pub fn attachment(&mut self, filename: &str) -> &mut Self {
+        let value = headers::content_disposition("attachment", filename);
+        return self.set_header("Content-Disposition", &value);
+    }
+
+    /// Overrides the reason phrase of the status line while keeping the status code. Example:
+    /// `HTTP/1.1 200 Everything Fine`. Control characters are removed so that the status line
+    /// can't be broken.
+    pub fn set_status_reason(&mut self, reason: &str) -> &mut Self {
+        let reason: String = reason.chars().filter(|character| !character.is_control()).collect();
+        self.status_reason = Some(reason);
+        return self;
+    }
+
+    /// Closes the connection after sending the response even if the client requested
+    /// keep-alive. Next request of the connection is not read.
+    pub fn close_connection(&mut self) -> &mut Self {
+        self.request.context.dont_wait();
+        return self;
+    }
+
     pub fn html<T: StatusCode>(&mut self, status: T, text: String) -> &mut Self {
         self.set_content(status.to_usize(), text);
         self.set_content_type("text/html");
@@ -76,14 +182,294 @@ impl Response {
         return self;
     }
 
+    /// Sets the status code and the binary response body
+    pub fn bytes<T: StatusCode>(&mut self, status: T, bytes: Vec<u8>) -> &mut Self {
+        self.set_content(status.to_usize(), String::new());
+        self.fixed_bytes = Some(bytes);
+        return self;
+    }
+
+    /// Sends the file with `ETag` and `Last-Modified` headers. Single `Range` request is served as
+    /// `206 Partial Content`. If `If-Range` is present, the range is only served if it matches
+    /// the current `ETag` or `Last-Modified` value, otherwise the full file is sent.
+    /// `304 Not Modified` is sent if `If-None-Match` matches the current `ETag` and
+    /// `412 Precondition Failed` if `If-Match` doesn't match. The file is streamed without
+    /// loading it to the memory. Directory is served using `index.html` or the listing if
+    /// `Config::auto_index` is enabled.
+    pub fn send_file(&mut self, path: &str) {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                self.send_directory(path);
+                return;
+            }
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => {
+                self.send_error(Status::NotFound);
+                return;
+            }
+        };
+
+        // File is streamed, so a range of a large file doesn't load the whole file to the memory
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => {
+                self.send_error(Status::InternalServerError);
+                return;
+            }
+        };
+
+        let length = metadata.len();
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+        let modified_seconds = match modified.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs(),
+            Err(_) => 0,
+        };
+
+        let etag = format!("\"{:x}-{:x}\"", modified_seconds, length);
+        let last_modified = headers::http_date(modified);
+
+        self.set_header("Accept-Ranges", "bytes");
+        self.set_header("ETag", &etag);
+        self.set_header("Last-Modified", &last_modified);
+        self.set_content_type(content_type_from_path(path));
+
+        if !self.check_if_match(&etag) {
+            return;
+        }
+
+        if headers::if_none_match(&self.request.headers, &etag) {
+            if self.request.method == "GET" || self.request.method == "HEAD" {
+                self.bytes(Status::NotModified, Vec::new()).send();
+            } else {
+                self.send_error(Status::PreconditionFailed);
+            }
+            return;
+        }
+
+        let mut byte_range = None;
+        if let Some(range) = self.request.headers.get("Range").and_then(|values| values.first()) {
+            if self.if_range_matches(&etag, &last_modified) {
+                byte_range = headers::parse_byte_range(range, length);
+            }
+        }
+
+        match byte_range {
+            Some(ByteRange::Satisfiable(start, end)) => {
+                let content_range = format!("bytes {}-{}/{}", start, end, length);
+                self.set_header("Content-Range", &content_range);
+                self.set_content(Status::PartialContent.to_usize(), String::new());
+                self.fixed_file = Some(FileBody { file: BodyFile::Regular(file), start, length: end - start + 1 });
+            }
+
+            Some(ByteRange::Unsatisfiable) => {
+                let content_range = format!("bytes */{}", length);
+                self.set_header("Content-Range", &content_range);
+                self.bytes(Status::RangeNotSatisfiable, Vec::new());
+            }
+
+            None => {
+                self.set_content(Status::Ok.to_usize(), String::new());
+                self.fixed_file = Some(FileBody { file: BodyFile::Regular(file), start: 0, length });
+            }
+        }
+
+        self.send();
+    }
+
+    /// Sends `412 Precondition Failed` and returns false if `If-Match` header of the request
+    /// doesn't match the current entity tag. Used for the optimistic concurrency of unsafe
+    /// methods such as `PUT` and `DELETE`.
+    ///
+    /// ```rust
+    /// use rusty_web::request::Request;
+    /// use rusty_web::response::Response;
+    /// use rusty_web::status::Status;
+    ///
+    /// fn update_article(request: Request, mut response: Response) {
+    ///     let current_etag = "\"v2\"";
+    ///     if !response.check_if_match(current_etag) {
+    ///         return;
+    ///     }
+    ///
+    ///     response.json(Status::Ok, "{}".to_string()).send();
+    /// }
+    /// ```
+    pub fn check_if_match(&mut self, etag: &str) -> bool {
+        if headers::if_match(&self.request.headers, etag) {
+            return true;
+        }
+
+        self.send_error(Status::PreconditionFailed);
+        return false;
+    }
+
+    /// Sends `index.html` of the directory or the listing of the directory entries
+    fn send_directory(&mut self, path: &str) {
+        let index_path = std::path::Path::new(path).join("index.html");
+        if index_path.is_file() {
+            self.send_file(&index_path.to_string_lossy());
+            return;
+        }
+
+        if !self.request.context.config.auto_index {
+            self.send_error(Status::NotFound);
+            return;
+        }
+
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => {
+                self.send_error(Status::InternalServerError);
+                return;
+            }
+        };
+
+        let mut names: Vec<String> = Vec::new();
+        for entry in entries.flatten() {
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() {
+                name.push('/');
+            }
+            names.push(name);
+        }
+        names.sort();
+
+        let title = format!("Index of {}", escape_html(&self.request.pathname));
+        let mut html = format!("<!DOCTYPE html>\n<html>\n<head><title>{}</title></head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+                               title, title);
+        if self.request.pathname != "/" {
+            html.push_str("<li><a href=\"../\">../</a></li>\n");
+        }
+
+        // Links are absolute, so they work even if the pathname doesn't end with `/`
+        let pathname = self.request.pathname.clone();
+        for name in names {
+            let mut href = join_path(&pathname, &url_encode(name.trim_end_matches('/')));
+            if name.ends_with('/') {
+                href.push('/');
+            }
+            html.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", escape_html(&href), escape_html(&name)));
+        }
+        html.push_str("</ul>\n</body>\n</html>\n");
+
+        self.html(Status::Ok, html).send();
+    }
+
+    /// Streams the temporary file as the response body and deletes it after the response is
+    /// sent. For example, to send back the body read using `Request::body`.
+    pub fn send_temp_file<T: StatusCode>(&mut self, status: T, temp_file: NamedTempFile) {
+        let length = match temp_file.as_file().metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(_) => {
+                self.send_error(Status::InternalServerError);
+                return;
+            }
+        };
+
+        self.set_content(status.to_usize(), String::new());
+        self.fixed_file = Some(FileBody { file: BodyFile::Temp(temp_file), start: 0, length });
+        if !self.content_type().is_some() {
+            self.set_content_type("application/octet-stream");
+        }
+
+        self.send();
+    }
+
+    /// Returns true if there is no `If-Range` header or it matches the given validators.
+    /// Entity tags are compared using strong comparison, so weak tags never match.
+    fn if_range_matches(&self, etag: &str, last_modified: &str) -> bool {
+        let if_range = match self.request.headers.get("If-Range").and_then(|values| values.first()) {
+            Some(value) => value.trim(),
+            None => return true,
+        };
+
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            return headers::etag_strong_match(if_range, etag);
+        }
+
+        return if_range == last_modified;
+    }
+
+    /// Sends the error response using the error view or the error renderer of the server config
+    pub fn send_error<T: StatusCode>(&mut self, status: T) {
+        let status = status.to_usize();
+        if !self.in_error_view && self.send_error_view(status) {
+            return;
+        }
+
+        let error_renderer = self.request.context.config.error_renderer;
+        let (body, content_type) = error_renderer(status, &self.request);
+
+        self.set_content(status, body);
+        self.set_content_type(&content_type);
+        self.send();
+    }
+
+    /// Runs the error view registered for the status class. Returns true if the view sent the
+    /// response. Panic of the error view is treated as not sent.
+    fn send_error_view(&mut self, status: usize) -> bool {
+        let view = match self.request.context.config.error_views.get(&(status / 100)) {
+            Some(view) => *view,
+            None => return false,
+        };
+
+        let mut response = Response::new(self.request.clone());
+        response.status = Some(status);
+        response.in_error_view = true;
+
+        let request = self.request.clone();
+        if catch_unwind(AssertUnwindSafe(|| view(request, response))).is_err() {
+            eprintln!("Error view panicked while serving status {}", status);
+        }
+
+        if self.request.context.is_response_sent(self.request.sequence) {
+            self.sent = true;
+            return true;
+        }
+
+        return false;
+    }
+
+    /// Sets the status code and the response body. Status code must be in range of 100 to 599.
+    /// Otherwise, `500 Internal Server Error` is used instead.
     pub fn set_content(&mut self, status: usize, text: String) -> &mut Self {
+        let mut status = status;
+        if !is_valid_status_code(status) {
+            eprintln!("Invalid status code {}. Using {} instead.", status, Status::InternalServerError.to_usize());
+            status = Status::InternalServerError.to_usize();
+        }
+
+        // Headers are always written with the response, even if the view hasn't set any
+        self.init_headers();
         self.status = Some(status);
         self.fixed_content = Some(text);
+        self.fixed_bytes = None;
+        self.fixed_file = None;
         return self;
     }
 
+    /// Returns true if the response is already written to the connection
+    pub fn is_sent(&self) -> bool {
+        return self.sent;
+    }
+
+    /// Writes the response to the connection. A response can only be sent once and only while
+    /// its request is being served. Otherwise, the response is rejected so that the responses of
+    /// the pipelined requests are not mixed up.
     pub fn send(&mut self) {
+        if self.sent {
+            eprintln!("Response is already sent");
+            return;
+        }
+
+        if !self.request.context.is_current_request(self.request.sequence) {
+            eprintln!("Response can't be sent after the next request of the connection is started");
+            return;
+        }
+
         if self.status.is_some() {
+            self.sent = true;
+            self.request.context.response_sequence.store(self.request.sequence, Ordering::Relaxed);
             let request = &self.request;
             let access_from: String;
 
@@ -97,73 +483,157 @@ impl Response {
                 }
             }
 
-            println!("{} - \"{} {} {}\"", access_from, request.method, request.pathname,
-                     self.status.unwrap());
+            if let Some(metrics) = &request.context.config.metrics {
+                metrics.response_sent(self.status.unwrap());
+            }
+
+            println!("{} - \"{} {} {}\" {}", access_from, request.method, request.pathname,
+                     self.status.unwrap(), request.request_id);
             self.write_http();
         }
     }
 
+    /// Returns the length of the response body in bytes. Text is counted in bytes of UTF-8, not in
+    /// characters.
+    fn body_length(&self) -> u64 {
+        if let Some(file_body) = &self.fixed_file {
+            return file_body.length;
+        }
+
+        if let Some(bytes) = &self.fixed_bytes {
+            return bytes.len() as u64;
+        }
+
+        return match &self.fixed_content {
+            Some(content) => content.len() as u64,
+            None => 0,
+        };
+    }
+
     fn write_http(&mut self) {
+        // Body ignored by the view is discarded, so the keep-alive connection stays in sync
+        if headers::is_keep_alive(&self.request.headers) {
+            self.request.discard_body();
+        }
+
         let should_close = self.request.should_close_connection();
+        // Connection is handed over to the view after switching the protocol
+        let is_upgrade = self.status == Some(Status::SwitchingProtocols.to_usize())
+            && self.request.upgrade_protocol().is_some();
+        // Informational, `204 No Content` and `304 Not Modified` responses never have a body
+        let has_body = !matches!(self.status, Some(100..=199 | 204 | 304));
+        let content_length = self.body_length().to_string();
 
         let headers = self.headers.as_mut().expect("Response headers missing.");
-        let content_length = format!("{}", self.fixed_content.as_ref()
-            .expect("Fixed content is missing.").len());
-        headers.insert("Content-Length".to_string(), vec![content_length]);
+        // Headers set by the view take precedence over the server defaults
+        for (name, value) in &self.request.context.config.default_headers {
+            let overridden = headers.keys().any(|key| key.eq_ignore_ascii_case(name));
+            if !overridden {
+                headers.insert(name.to_string(), vec![value.to_string()]);
+            }
+        }
 
-        if !should_close {
-            headers.insert("Connection".to_string(), vec!["keep-alive".to_string()]);
+        if !headers.keys().any(|key| key.eq_ignore_ascii_case("X-Request-Id")) {
+            headers.insert("X-Request-Id".to_string(), vec![self.request.request_id.clone()]);
         }
 
-        // Write repose headers
-        let headers = self.prepare_raw_headers();
+        // Content-Length set by the view is replaced with the actual body length, otherwise the
+        // client would wait for more bytes or read the rest as the next response
+        let manual_length = headers.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+            .map(|(_, values)| values.clone());
+        // HEAD response may advertise the length of the GET body without sending it
+        let keep_manual_length = self.request.method == "HEAD" && manual_length.is_some();
 
-        let cloned_stream = self.request.stream.try_clone();
-        if !cloned_stream.is_ok() {
-            println!("Connection closed");
-            self.request.context.dont_wait();
-            return;
+        if !has_body || !keep_manual_length {
+            headers.retain(|name, _| !name.eq_ignore_ascii_case("Content-Length"));
+        }
+
+        if has_body && !keep_manual_length {
+            if let Some(values) = manual_length {
+                if values != [content_length.as_str()] {
+                    eprintln!("Content-Length {:?} doesn't match the body length {}. It is corrected.",
+                              values, content_length);
+                }
+            }
+
+            headers.insert("Content-Length".to_string(), vec![content_length]);
+        }
+
+        // Connection decision is always sent, so the client doesn't wait on a closing connection
+        if is_upgrade {
+            headers.insert("Connection".to_string(), vec!["Upgrade".to_string()]);
+        } else {
+            let connection_type = if should_close { "close" } else { "keep-alive" };
+            headers.insert("Connection".to_string(), vec![connection_type.to_string()]);
         }
 
-        let mut buf_writer = BufWriter::new(cloned_stream.unwrap());
+        // Write repose headers
+        let headers = self.prepare_raw_headers();
+
+        // Writes through the shared stream without duplicating it
+        let stream = self.request.stream.clone();
+        let buffer_size = self.request.context.config.write_buffer_size;
+        let mut buf_writer = BufWriter::with_capacity(buffer_size, stream.as_ref());
         match buf_writer.write_all(headers.as_bytes()) {
             Ok(_) => {}
             Err(_) => {
-                println!("Connection closed");
-                self.request.context.dont_wait();
+                self.abort_connection();
                 return;
             }
         }
 
         // Write response body
-        if self.request.method != "HEAD" {
-            if let Some(content) = &self.fixed_content {
-                buf_writer.write_all(content.as_bytes()).unwrap();
+        if has_body && self.request.method != "HEAD" {
+            let write_result = match (self.fixed_file.as_mut(), &self.fixed_bytes) {
+                (Some(file_body), _) => write_file_body(file_body, &mut buf_writer),
+                (None, Some(bytes)) => buf_writer.write_all(bytes),
+                (None, None) => match &self.fixed_content {
+                    Some(content) => buf_writer.write_all(content.as_bytes()),
+                    None => Ok(()),
+                },
+            };
+
+            if !write_result.is_ok() {
+                self.abort_connection();
+                return;
             }
         }
 
         // Flush the buffer
         if !buf_writer.flush().is_ok() {
-            print!("Connection closed");
-            self.request.context.dont_wait();
+            self.abort_connection();
+            return;
         };
 
-        if should_close {
+        if is_upgrade {
+            // Next request is not read, but the stream is kept open for the view
+            self.request.context.dont_wait();
+        } else if should_close {
             let _ = self.request.stream.shutdown(Shutdown::Both);
             self.request.context.dont_wait();
         }
     }
 
+    /// Closes the connection after write failure. Response may be partially written, so the
+    /// stream can't be reused for the next request even if it was keep-alive.
+    fn abort_connection(&mut self) {
+        println!("Connection closed");
+        let _ = self.request.stream.shutdown(Shutdown::Both);
+        self.request.context.dont_wait();
+    }
+
     fn prepare_raw_headers(&mut self) -> String {
         let status_code = self.status.expect("Status code not set.");
 
-        let mut status_text = Status::status_text(status_code);
+        // Status codes in the valid range which are not known by `Status` are sent as custom status
+        let mut status_text = self.status_reason.clone().or(Status::status_text(status_code));
         if !status_text.is_some() {
             status_text = Some("Custom Status".to_string());
         }
 
         // Header start
-        let mut raw_headers = format!("HTTP/1.1 {} {}\r\n", self.status.unwrap(), status_text.unwrap());
+        let mut raw_headers = format!("{} {} {}\r\n", self.version, self.status.unwrap(), status_text.unwrap());
 
         if let Some(headers) = &self.headers {
             for header_name in headers.keys() {
@@ -181,3 +651,622 @@ impl Response {
         return raw_headers;
     }
 }
+
+/// Writes exactly the length of the file body read before sending the headers. Fails if the
+/// file became shorter, since the connection can't be kept in sync.
+fn write_file_body<W: Write>(file_body: &mut FileBody, writer: &mut W) -> io::Result<()> {
+    let file = match &mut file_body.file {
+        BodyFile::Temp(temp_file) => temp_file.as_file_mut(),
+        BodyFile::Regular(file) => file,
+    };
+    file.seek(SeekFrom::Start(file_body.start))?;
+
+    let written = io::copy(&mut file.take(file_body.length), writer)?;
+    if written != file_body.length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "File is shorter than its length"));
+    }
+
+    return Ok(());
+}
+
+/// Returns the content type from the file extension. Unknown files are sent as binary.
+fn content_type_from_path(path: &str) -> &'static str {
+    let extension = match path.rsplit_once('.') {
+        Some((_, extension)) => extension.to_lowercase(),
+        None => return "application/octet-stream",
+    };
+
+    return match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    };
+}
+
+/// Escapes the characters which have special meaning in HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character),
+        }
+    }
+
+    return escaped;
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tempfile::NamedTempFile;
+    use crate::headers::{Headers, SecurityHeaders};
+    use crate::paths::{Path, Paths};
+    use crate::request::Request;
+    use crate::response::Response;
+    use crate::server::{Config, Context};
+    use crate::status::Status;
+    use crate::testing::{handle_raw, handle_raw_with_config};
+
+    /// Returns response of connected local stream and the client stream
+    fn connected_response(method: &str, headers: Headers) -> (Response, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let context = Arc::new(Context::new(Arc::new(Config::default())));
+
+        let request = Request::new(context, stream, method.to_string(), "/".to_string(), headers,
+                                   Arc::new(AtomicBool::new(true)), Arc::new(AtomicBool::new(false)));
+        return (Response::new(request), client);
+    }
+
+    /// Returns response of connected local stream. Nothing is sent to the client.
+    fn test_response(method: &str) -> Response {
+        let (response, _client) = connected_response(method, HashMap::new());
+        return response;
+    }
+
+    #[test]
+    fn test_known_status_line() {
+        let mut response = test_response("GET");
+        response.html(Status::NotFound, "".to_string());
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_custom_status_line() {
+        let mut response = test_response("GET");
+        response.html(299, "".to_string());
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.1 299 Custom Status\r\n"));
+    }
+
+    #[test]
+    fn test_status_reason() {
+        let mut response = test_response("GET");
+        response.html(Status::Ok, "".to_string());
+        response.set_status_reason("Everything Fine");
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.1 200 Everything Fine\r\n"));
+
+        response.html(299, "".to_string());
+        response.set_status_reason("Partly\r\nDone");
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.1 299 PartlyDone\r\n"));
+    }
+
+    #[test]
+    fn test_out_of_range_status() {
+        let mut response = test_response("GET");
+        response.html(999, "".to_string());
+        assert_eq!(Some(500), response.status);
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    }
+
+    #[test]
+    fn test_reason_phrase_status_line() {
+        let mut response = test_response("GET");
+        response.html(Status::MultiStatus, "".to_string());
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.1 207 Multi-Status\r\n"));
+
+        response.html(Status::ImaTeaPot, "".to_string());
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.1 418 I'm a teapot\r\n"));
+    }
+
+    #[test]
+    fn test_write_failure_closes_connection() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["keep-alive".to_string()]);
+
+        let (mut response, client) = connected_response("GET", headers);
+        assert_eq!(false, response.request.should_close_connection());
+
+        // Client disconnects before the response is written
+        drop(client);
+
+        let body = "a".repeat(16 * 1024 * 1024);
+        response.html(Status::Ok, body).send();
+        assert_eq!(false, response.request.context.accept_next.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_single_content_type() {
+        let mut response = test_response("GET");
+        response.html(Status::Ok, "".to_string());
+        response.add_header("Content-Type", "text/plain");
+        response.add_header("content-type", "application/xml");
+        assert_eq!(Some("application/xml"), response.content_type());
+
+        let raw_headers = response.prepare_raw_headers().to_lowercase();
+        assert_eq!(1, raw_headers.matches("content-type:").count());
+    }
+
+    /// Sends the file with the given request headers and returns the raw response
+    fn send_file_response(path: &str, request_headers: Vec<(&str, &str)>) -> String {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["close".to_string()]);
+        for (name, value) in request_headers {
+            headers.insert(name.to_string(), vec![value.to_string()]);
+        }
+
+        let (mut response, mut client) = connected_response("GET", headers);
+        response.send_file(path);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        return raw_response;
+    }
+
+    /// Returns the value of the header from the raw response
+    fn response_header(raw_response: &str, name: &str) -> Option<String> {
+        for line in raw_response.split("\r\n") {
+            if let Some((header_name, value)) = line.split_once(": ") {
+                if header_name == name {
+                    return Some(value.to_string());
+                }
+            }
+        }
+
+        return None;
+    }
+
+    #[test]
+    fn test_send_file_range() {
+        let mut file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        file.write_all(b"0123456789").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let raw_response = send_file_response(path, vec![("Range", "bytes=2-5")]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert_eq!(Some("bytes 2-5/10".to_string()), response_header(&raw_response, "Content-Range"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\n2345"));
+
+        let raw_response = send_file_response(path, vec![("Range", "bytes=20-")]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"));
+    }
+
+    #[test]
+    fn test_send_file_large_range() {
+        let content: Vec<u8> = (0..4 * 1024 * 1024).map(|index| b'a' + (index % 26) as u8).collect();
+        let mut file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        file.write_all(&content).unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let raw_response = send_file_response(path, vec![("Range", "bytes=3000000-3000009")]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert_eq!(Some("10".to_string()), response_header(&raw_response, "Content-Length"));
+        let expected = String::from_utf8(content[3000000..3000010].to_vec()).unwrap();
+        assert_eq!(true, raw_response.ends_with(&format!("\r\n\r\n{}", expected)));
+    }
+
+    #[test]
+    fn test_send_file_if_range_matches() {
+        let mut file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        file.write_all(b"0123456789").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let raw_response = send_file_response(path, vec![]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let etag = response_header(&raw_response, "ETag").unwrap();
+        let last_modified = response_header(&raw_response, "Last-Modified").unwrap();
+
+        let raw_response = send_file_response(path, vec![("Range", "bytes=-3"), ("If-Range", &etag)]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\n789"));
+
+        let raw_response = send_file_response(path, vec![("Range", "bytes=-3"), ("If-Range", &last_modified)]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    }
+
+    #[test]
+    fn test_send_file_if_range_not_matches() {
+        let mut file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        file.write_all(b"0123456789").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let raw_response = send_file_response(path, vec![("Range", "bytes=0-1"), ("If-Range", "\"outdated\"")]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(None, response_header(&raw_response, "Content-Range"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\n0123456789"));
+
+        let raw_response = send_file_response(path, vec![("Range", "bytes=0-1"),
+                                                         ("If-Range", "Thu, 01 Jan 1970 00:00:00 GMT")]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_send_file_if_none_match() {
+        let mut file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        file.write_all(b"0123456789").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let raw_response = send_file_response(path, vec![]);
+        let etag = response_header(&raw_response, "ETag").unwrap();
+
+        let weak_etag = format!("W/{}", etag);
+        for if_none_match in [etag.as_str(), weak_etag.as_str(), "\"outdated\", *"] {
+            let raw_response = send_file_response(path, vec![("If-None-Match", if_none_match)]);
+            assert_eq!(true, raw_response.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+            assert_eq!(Some(etag.clone()), response_header(&raw_response, "ETag"));
+            assert_eq!(true, raw_response.ends_with("\r\n\r\n"));
+        }
+
+        let raw_response = send_file_response(path, vec![("If-None-Match", "\"outdated\"")]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        // Weak tag never matches `If-Range`
+        let raw_response = send_file_response(path, vec![("Range", "bytes=0-1"), ("If-Range", &weak_etag)]);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_check_if_match() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("If-Match".to_string(), vec!["\"v1\", \"v2\"".to_string()]);
+
+        let (mut response, mut client) = connected_response("PUT", headers.clone());
+        assert_eq!(Some(vec!["\"v1\"".to_string(), "\"v2\"".to_string()]), response.request.if_match());
+        assert_eq!(true, response.check_if_match("\"v2\""));
+        response.html(Status::Ok, "Updated".to_string()).send();
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        let (mut response, mut client) = connected_response("DELETE", headers);
+        assert_eq!(false, response.check_if_match("\"v3\""));
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 412 Precondition Failed\r\n"));
+
+        let response = test_response("PUT");
+        assert_eq!(None, response.request.if_match());
+    }
+
+    /// Sends the directory at the pathname and returns the raw response
+    fn send_directory_response(directory: &str, pathname: &str, auto_index: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let config = Config { auto_index, ..Config::default() };
+        let context = Arc::new(Context::new(Arc::new(config)));
+        let request = Request::new(context, stream, "GET".to_string(), pathname.to_string(), HashMap::new(),
+                                   Arc::new(AtomicBool::new(true)), Arc::new(AtomicBool::new(false)));
+
+        let mut response = Response::new(request);
+        response.send_file(directory);
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        return raw_response;
+    }
+
+    #[test]
+    fn test_directory_listing() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("a.txt"), "a").unwrap();
+        std::fs::write(directory.path().join("<b>&c.txt"), "b").unwrap();
+        std::fs::create_dir(directory.path().join("sub dir")).unwrap();
+        let path = directory.path().to_str().unwrap();
+
+        let raw_response = send_directory_response(path, "/files", true);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, raw_response.contains("<title>Index of /files</title>"));
+        assert_eq!(true, raw_response.contains("<li><a href=\"../\">../</a></li>"));
+        assert_eq!(true, raw_response.contains("<li><a href=\"/files/a.txt\">a.txt</a></li>"));
+        assert_eq!(true, raw_response.contains("<li><a href=\"/files/%3Cb%3E%26c.txt\">&lt;b&gt;&amp;c.txt</a></li>"));
+        assert_eq!(true, raw_response.contains("<li><a href=\"/files/sub%20dir/\">sub dir/</a></li>"));
+
+        let raw_response = send_directory_response(path, "/files", false);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert_eq!(false, raw_response.contains("a.txt"));
+
+        std::fs::write(directory.path().join("index.html"), "Index Page").unwrap();
+        let raw_response = send_directory_response(path, "/files", true);
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\nIndex Page"));
+    }
+
+    #[test]
+    fn test_response_sent_once() {
+        let mut response = test_response("GET");
+        response.html(Status::Ok, "".to_string()).send();
+        assert_eq!(true, response.is_sent());
+
+        // Response of the previous request is rejected once the next request is started
+        let mut stale_response = Response::new(response.request.clone());
+        stale_response.html(Status::Ok, "".to_string());
+        stale_response.request.context.request_sequence.fetch_add(1, Ordering::Relaxed);
+        stale_response.send();
+        assert_eq!(false, stale_response.is_sent());
+    }
+
+    fn large_view(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "a".repeat(1024 * 1024)).send();
+    }
+
+    #[test]
+    fn test_write_buffer_size() {
+        let config = Config {
+            write_buffer_size: 64 * 1024,
+            ..Config::default()
+        };
+
+        let paths: Paths = vec![Path::new("/", large_view)];
+        let raw_response = handle_raw_with_config(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n", paths, config);
+        let raw_response = String::from_utf8(raw_response).unwrap();
+
+        let (headers, body) = raw_response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(true, headers.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(1024 * 1024, body.len());
+        assert_eq!(true, body.bytes().all(|byte| byte == b'a'));
+    }
+
+    fn framed_view(_request: Request, mut response: Response) {
+        response.set_header("X-Frame-Options", "SAMEORIGIN");
+        response.html(Status::Ok, "".to_string()).send();
+    }
+
+    fn default_headers_config() -> Config {
+        return Config {
+            default_headers: vec![
+                ("X-Content-Type-Options".to_string(), "nosniff".to_string()),
+                ("X-Frame-Options".to_string(), "DENY".to_string()),
+            ],
+            ..Config::default()
+        };
+    }
+
+    #[test]
+    fn test_default_headers() {
+        let paths: Paths = vec![Path::new("/", large_view)];
+        let raw_response = handle_raw_with_config(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n", paths, default_headers_config());
+        let raw_response = String::from_utf8(raw_response).unwrap();
+        assert_eq!(Some("nosniff".to_string()), response_header(&raw_response, "X-Content-Type-Options"));
+        assert_eq!(Some("DENY".to_string()), response_header(&raw_response, "X-Frame-Options"));
+
+        // Header set by the view wins
+        let paths: Paths = vec![Path::new("/", framed_view)];
+        let raw_response = handle_raw_with_config(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n", paths, default_headers_config());
+        let raw_response = String::from_utf8(raw_response).unwrap();
+        assert_eq!(Some("nosniff".to_string()), response_header(&raw_response, "X-Content-Type-Options"));
+        assert_eq!(Some("SAMEORIGIN".to_string()), response_header(&raw_response, "X-Frame-Options"));
+    }
+
+    #[test]
+    fn test_security_headers() {
+        let security_headers = SecurityHeaders {
+            frame_options: None,
+            ..SecurityHeaders::default()
+        };
+
+        let config = Config {
+            default_headers: security_headers.to_headers(),
+            ..Config::default()
+        };
+
+        let paths: Paths = vec![Path::new("/", large_view)];
+        let raw_response = handle_raw_with_config(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n", paths, config);
+        let raw_response = String::from_utf8(raw_response).unwrap();
+        assert_eq!(Some("max-age=31536000".to_string()), response_header(&raw_response, "Strict-Transport-Security"));
+        assert_eq!(Some("nosniff".to_string()), response_header(&raw_response, "X-Content-Type-Options"));
+        assert_eq!(Some("default-src 'self'".to_string()), response_header(&raw_response, "Content-Security-Policy"));
+        assert_eq!(None, response_header(&raw_response, "X-Frame-Options"));
+    }
+
+    fn echo(mut request: Request, mut response: Response) {
+        match request.body() {
+            Some(body) => response.send_temp_file(Status::Ok, body),
+            None => response.send_error(Status::BadRequest),
+        }
+    }
+
+    #[test]
+    fn test_send_temp_file() {
+        let paths: Paths = vec![Path::new("/", echo)];
+        let raw_request = b"POST / HTTP/1.1\r\nContent-Length: 11\r\nConnection: close\r\n\r\nhello world";
+        let raw_response = String::from_utf8(handle_raw(raw_request, paths)).unwrap();
+
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(Some("11".to_string()), response_header(&raw_response, "Content-Length"));
+        assert_eq!(Some("application/octet-stream".to_string()), response_header(&raw_response, "Content-Type"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\nhello world"));
+
+        // Temporary file is deleted after sending
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"data").unwrap();
+        let temp_path = temp_file.path().to_path_buf();
+
+        let (mut response, mut client) = connected_response("GET", HashMap::new());
+        response.send_temp_file(Status::Ok, temp_file);
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(true, raw_response.ends_with("\r\n\r\ndata"));
+        assert_eq!(false, temp_path.exists());
+    }
+
+    #[test]
+    fn test_connection_header() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["keep-alive".to_string()]);
+
+        let (mut response, mut client) = connected_response("GET", headers);
+        response.html(Status::Ok, "".to_string()).send();
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(Some("keep-alive".to_string()), response_header(&raw_response, "Connection"));
+
+        let (mut response, mut client) = connected_response("GET", HashMap::new());
+        response.html(Status::Ok, "".to_string()).send();
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(Some("close".to_string()), response_header(&raw_response, "Connection"));
+    }
+
+    #[test]
+    fn test_connection_token_list() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["Keep-Alive, Upgrade".to_string()]);
+
+        let (mut response, mut client) = connected_response("GET", headers);
+        response.html(Status::Ok, "".to_string()).send();
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(Some("keep-alive".to_string()), response_header(&raw_response, "Connection"));
+    }
+
+    #[test]
+    fn test_upgrade_response() {
+        let mut headers: Headers = HashMap::new();
+        headers.insert("Connection".to_string(), vec!["Upgrade".to_string()]);
+        headers.insert("Upgrade".to_string(), vec!["websocket".to_string()]);
+
+        let (mut response, mut client) = connected_response("GET", headers);
+        let mut stream = response.request.stream.try_clone().unwrap();
+        response.set_header("Upgrade", "websocket");
+        response.bytes(Status::SwitchingProtocols, Vec::new()).send();
+        assert_eq!(false, response.request.context.accept_next.load(Ordering::Relaxed));
+        drop(response);
+
+        // Stream is still open for the upgraded protocol
+        stream.write_all(b"upgraded").unwrap();
+        drop(stream);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert_eq!(Some("Upgrade".to_string()), response_header(&raw_response, "Connection"));
+        assert_eq!(None, response_header(&raw_response, "Content-Length"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\nupgraded"));
+    }
+
+    #[test]
+    fn test_bytes_without_headers() {
+        let (mut response, mut client) = connected_response("GET", HashMap::new());
+        response.bytes(Status::Ok, b"hi".to_vec()).send();
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(Some("2".to_string()), response_header(&raw_response, "Content-Length"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\nhi"));
+    }
+
+    #[test]
+    fn test_multibyte_content_length() {
+        let (mut response, mut client) = connected_response("GET", HashMap::new());
+        response.html(Status::Ok, "héllo 👋".to_string()).send();
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        // 7 characters but 11 bytes
+        assert_eq!(Some("11".to_string()), response_header(&raw_response, "Content-Length"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\nhéllo 👋"));
+    }
+
+    #[test]
+    fn test_wrong_content_length_corrected() {
+        let (mut response, mut client) = connected_response("GET", HashMap::new());
+        response.set_header("content-length", "3");
+        response.html(Status::Ok, "Hello World".to_string()).send();
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(1, raw_response.to_lowercase().matches("content-length").count());
+        assert_eq!(Some("11".to_string()), response_header(&raw_response, "Content-Length"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\nHello World"));
+    }
+
+    #[test]
+    fn test_head_keeps_content_length() {
+        let (mut response, mut client) = connected_response("HEAD", HashMap::new());
+        response.set_header("Content-Length", "1234");
+        response.html(Status::Ok, "".to_string()).send();
+        drop(response);
+
+        let mut raw_response = String::new();
+        client.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(Some("1234".to_string()), response_header(&raw_response, "Content-Length"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_bodyless_status() {
+        for status in [Status::NoContent, Status::NotModified] {
+            let (mut response, mut client) = connected_response("GET", HashMap::new());
+            response.set_header("Content-Length", "5");
+            response.html(status, "ignored".to_string()).send();
+            drop(response);
+
+            let mut raw_response = String::new();
+            client.read_to_string(&mut raw_response).unwrap();
+            assert_eq!(None, response_header(&raw_response, "Content-Length"));
+            assert_eq!(true, raw_response.ends_with("\r\n\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_response_version() {
+        let paths: Paths = vec![Path::new("/", large_view)];
+        let raw_response = handle_raw_with_config(b"HEAD / HTTP/1.0\r\n\r\n", paths, Config::default());
+        assert_eq!(true, raw_response.starts_with(b"HTTP/1.0 200 OK\r\n"));
+
+        let paths: Paths = vec![Path::new("/", large_view)];
+        let raw_response = handle_raw_with_config(b"HEAD / HTTP/1.1\r\n\r\n", paths, Config::default());
+        assert_eq!(true, raw_response.starts_with(b"HTTP/1.1 200 OK\r\n"));
+
+        let mut response = test_response("GET");
+        response.html(Status::Ok, "".to_string());
+        response.version = "HTTP/1.0".to_string();
+        assert_eq!(true, response.prepare_raw_headers().starts_with("HTTP/1.0 200 OK\r\n"));
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::handler::ResultView;
+use crate::parser::{url_decode, url_encode};
+use crate::paths::{Middleware, Path, Paths, SinglePath};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Collects paths so that routes can be composed across modules before passing to the server.
+///
+/// ```rust
+/// use rusty_web::request::Request;
+/// use rusty_web::response::Response;
+/// use rusty_web::router::Router;
+/// use rusty_web::status::Status;
+///
+/// fn users(request: Request, mut response: Response) {
+///    response.json(Status::Ok, "[]".to_string()).send();
+/// }
+///
+/// let mut api = Router::new();
+/// api.add("GET", "/users", users);
+///
+/// let mut router = Router::new();
+/// router.mount("/api", api);
+///
+/// let paths = router.into_paths();
+/// assert_eq!("/api/users", paths[0].name);
+/// ```
+pub struct Router {
+    paths: Paths,
+    /// Middlewares applied to all the paths of this router
+    middlewares: Vec<Middleware>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        return Self {
+            paths: Vec::new(),
+            middlewares: Vec::new(),
+        };
+    }
+
+    /// Adds middleware which runs before the views of all the paths in this router
+    pub fn middleware(&mut self, middleware: Middleware) -> &mut Self {
+        self.middlewares.push(middleware);
+        return self;
+    }
+
+    /// Adds the paths which share the same prefix and middlewares.
+    ///
+    /// ```rust
+    /// use rusty_web::request::Request;
+    /// use rusty_web::response::Response;
+    /// use rusty_web::router::Router;
+    ///
+    /// fn users(request: Request, response: Response) {}
+    ///
+    /// let mut router = Router::new();
+    /// router.group("/api", |group| {
+    ///     group.add("GET", "/users", users);
+    /// });
+    /// ```
+    pub fn group<F: FnOnce(&mut Router)>(&mut self, prefix: &str, build: F) -> &mut Self {
+        let mut group = Router::new();
+        build(&mut group);
+        return self.mount(prefix, group);
+    }
+
+    /// Adds a view which serves only the given request method
+    pub fn add(&mut self, method: &str, path: &str, view: fn(Request, Response)) -> &mut Self {
+        self.paths.push(Path::with_method(method, path, view));
+        return self;
+    }
+
+    /// Adds a view with route name which can be used to generate url with `url_for`.
+    /// Path may contain parameters prefixed with `:`. Example: `/users/:id`
+    pub fn add_named(&mut self, route_name: &str, method: &str, path: &str, view: fn(Request, Response)) -> &mut Self {
+        let mut path = Path::with_method(method, path, view);
+        path.route_name = Some(route_name.to_string());
+        self.paths.push(path);
+        return self;
+    }
+
+    /// Adds a view which returns the response instead of sending it. See `ResultView`
+    pub fn add_result(&mut self, method: &str, path: &str, view: ResultView) -> &mut Self {
+        let mut path = Path::with_result(path, view);
+        path.method = Some(method.to_uppercase());
+        self.paths.push(path);
+        return self;
+    }
+
+    /// Adds the path as it is. It is useful for the path with custom `RouteConfig`.
+    pub fn add_path(&mut self, path: SinglePath) -> &mut Self {
+        self.paths.push(path);
+        return self;
+    }
+
+    /// Adds a view which serves all the request methods
+    pub fn add_any(&mut self, path: &str, view: fn(Request, Response)) -> &mut Self {
+        self.paths.push(Path::new(path, view));
+        return self;
+    }
+
+    /// Moves all the paths of other router to this router
+    pub fn merge(&mut self, other: Router) -> &mut Self {
+        self.paths.extend(other.into_paths());
+        return self;
+    }
+
+    /// Moves all the paths of other router to this router. Each path is prefixed with `prefix`.
+    pub fn mount(&mut self, prefix: &str, other: Router) -> &mut Self {
+        for mut path in other.into_paths() {
+            path.name = join_path(prefix, &path.name);
+            self.paths.push(path);
+        }
+
+        return self;
+    }
+
+    /// Returns url of the named route by replacing `:name` parameters with the given values.
+    /// Returns `None` if the route name is unknown or any parameter value is missing.
+    ///
+    /// ```rust
+    /// use rusty_web::request::Request;
+    /// use rusty_web::response::Response;
+    /// use rusty_web::router::Router;
+    ///
+    /// fn user_detail(request: Request, response: Response) {}
+    ///
+    /// let mut router = Router::new();
+    /// router.add_named("user_detail", "GET", "/users/:id", user_detail);
+    ///
+    /// let url = router.url_for("user_detail", &[("id", "42")]);
+    /// assert_eq!(Some("/users/42".to_string()), url);
+    /// ```
+    pub fn url_for(&self, route_name: &str, params: &[(&str, &str)]) -> Option<String> {
+        for path in self.paths.iter() {
+            if path.route_name.as_deref() == Some(route_name) {
+                return fill_template(&path.name, params);
+            }
+        }
+
+        return None;
+    }
+
+    /// Matches the request method and pathname with the paths without serving the request.
+    /// Useful for testing the routes.
+    ///
+    /// ```rust
+    /// use rusty_web::request::Request;
+    /// use rusty_web::response::Response;
+    /// use rusty_web::router::{MatchResult, Router};
+    ///
+    /// fn user_detail(request: Request, response: Response) {}
+    ///
+    /// let mut router = Router::new();
+    /// router.add("GET", "/users/:id", user_detail);
+    ///
+    /// assert_eq!(true, matches!(router.match_route("GET", "/users/42"), MatchResult::Found(_)));
+    /// let allowed = vec!["GET".to_string(), "HEAD".to_string()];
+    /// assert_eq!(MatchResult::MethodNotAllowed(allowed), router.match_route("POST", "/users/42"));
+    /// assert_eq!(MatchResult::NotFound, router.match_route("GET", "/posts"));
+    /// ```
+    pub fn match_route(&self, method: &str, pathname: &str) -> MatchResult {
+        return match_paths(&self.paths, method, pathname);
+    }
+
+    pub fn paths(&self) -> &Paths {
+        return &self.paths;
+    }
+
+    /// Returns the paths which can be passed to the server. Router middlewares are added
+    /// before the middlewares of each path.
+    pub fn into_paths(self) -> Paths {
+        let mut paths = self.paths;
+
+        for path in paths.iter_mut() {
+            let mut middlewares = self.middlewares.clone();
+            middlewares.append(&mut path.middlewares);
+            path.middlewares = middlewares;
+        }
+
+        return paths;
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// Result of matching the request with the paths. See `Router::match_route`.
+#[derive(Debug, PartialEq)]
+pub enum MatchResult {
+    /// Path serving the request is found. Contains the path parameters.
+    Found(HashMap<String, String>),
+    /// Pathname is matched but the method is not served. Contains the sorted methods which
+    /// are served for the pathname.
+    MethodNotAllowed(Vec<String>),
+    NotFound,
+}
+
+/// Path which can be matched with the request. Implemented by the paths of both servers.
+pub trait Route {
+    /// Path template. Example: `/users/:id`
+    fn template(&self) -> &str;
+    /// Request method served by the path. `None` serves all the methods.
+    fn method(&self) -> Option<&str>;
+}
+
+impl<T> Route for Path<T> {
+    fn template(&self) -> &str {
+        return &self.name;
+    }
+
+    fn method(&self) -> Option<&str> {
+        return self.method.as_deref();
+    }
+}
+
+/// Matches the request method and pathname with the paths. The server dispatches the requests
+/// using the same matching.
+pub fn match_paths<R: Route>(paths: &[R], method: &str, pathname: &str) -> MatchResult {
+    let (_, result) = resolve_path(paths, method, pathname);
+    return result;
+}
+
+/// Same as `match_paths` but also returns the path which serves the request. If more than one
+/// path matches, the last one is used. `HEAD` requests are served by the `GET` path unless a
+/// `HEAD` path is matched.
+pub fn resolve_path<'a, R: Route>(paths: &'a [R], method: &str, pathname: &str) -> (Option<&'a R>, MatchResult) {
+    let mut found = None;
+    let mut get_found = None;
+    let mut allowed_methods = BTreeSet::new();
+    let is_head = method.eq_ignore_ascii_case("HEAD");
+
+    for path in paths.iter() {
+        let params = match match_template(path.template(), pathname) {
+            Some(params) => params,
+            None => continue,
+        };
+
+        match path.method() {
+            Some(path_method) if is_head && path_method.eq_ignore_ascii_case("GET") => {
+                get_found = Some((path, params));
+            }
+            Some(path_method) if !path_method.eq_ignore_ascii_case(method) => {
+                allowed_methods.insert(path_method.to_uppercase());
+            }
+            _ => {
+                found = Some((path, params));
+            }
+        }
+    }
+
+    if let Some((path, params)) = found.or(get_found) {
+        return (Some(path), MatchResult::Found(params));
+    }
+
+    if !allowed_methods.is_empty() {
+        // Response to `HEAD` is sent by the `GET` path
+        if allowed_methods.contains("GET") {
+            allowed_methods.insert("HEAD".to_string());
+        }
+
+        return (None, MatchResult::MethodNotAllowed(allowed_methods.into_iter().collect()));
+    }
+
+    return (None, MatchResult::NotFound);
+}
+
+/// Replaces `:name` segments of the path template with the percent encoded parameter values.
+/// Returns `None` if any parameter value is missing.
+pub fn fill_template(template: &str, params: &[(&str, &str)]) -> Option<String> {
+    let mut segments = Vec::new();
+
+    for segment in template.split('/') {
+        if let Some(param_name) = segment.strip_prefix(':') {
+            let value = params.iter()
+                .find(|(name, _)| *name == param_name)
+                .map(|(_, value)| *value);
+
+            match value {
+                Some(value) => {
+                    segments.push(url_encode(value));
+                }
+                None => {
+                    return None;
+                }
+            }
+        } else {
+            segments.push(segment.to_string());
+        }
+    }
+
+    return Some(segments.join("/"));
+}
+
+/// Matches the pathname with the path template. Segments prefixed with `:` matches any non empty
+/// segment. Returns the url decoded parameter values if matched.
+///
+/// Example: `/users/:id` matches `/users/42` with `id` as `42`
+pub fn match_template(template: &str, pathname: &str) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+
+    if !template.contains(':') {
+        if template == pathname {
+            return Some(params);
+        }
+
+        return None;
+    }
+
+    let template_segments: Vec<&str> = template.split('/').collect();
+    let path_segments: Vec<&str> = pathname.split('/').collect();
+
+    if template_segments.len() != path_segments.len() {
+        return None;
+    }
+
+    for (template_segment, path_segment) in template_segments.iter().zip(path_segments.iter()) {
+        if let Some(param_name) = template_segment.strip_prefix(':') {
+            if path_segment.is_empty() {
+                return None;
+            }
+
+            params.insert(param_name.to_string(), url_decode(path_segment));
+        } else if template_segment != path_segment {
+            return None;
+        }
+    }
+
+    return Some(params);
+}
+
+/// Joins the prefix and the path with single `/` between them.
+/// Example: `/api/` and `/users` becomes `/api/users`
+pub fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    let path = path.trim_start_matches('/');
+    return format!("{}/{}", prefix, path);
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use crate::paths::Flow;
+    use crate::request::Request;
+    use crate::response::Response;
+    use crate::router::{join_path, match_template, MatchResult, Router};
+    use crate::status::Status;
+    use crate::testing::handle_raw;
+
+    fn view(_request: Request, _response: Response) {}
+
+    fn ok_view(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "OK".to_string()).send();
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut router = Router::new();
+        router.add("GET", "/", view);
+
+        let mut other = Router::new();
+        other.add("post", "/about", view).add_any("/contact", view);
+
+        router.merge(other);
+        let paths = router.into_paths();
+
+        assert_eq!(3, paths.len());
+        assert_eq!("/about", paths[1].name);
+        assert_eq!(Some("POST".to_string()), paths[1].method);
+        assert_eq!(None, paths[2].method);
+    }
+
+    #[test]
+    fn test_mount() {
+        let mut users = Router::new();
+        users.add("GET", "/", view).add("GET", "/detail", view);
+
+        let mut router = Router::new();
+        router.add("GET", "/", view);
+        router.mount("/users/", users);
+        let paths = router.into_paths();
+
+        let names: Vec<&str> = paths.iter().map(|path| path.name.as_str()).collect();
+        assert_eq!(vec!["/", "/users/", "/users/detail"], names);
+    }
+
+    #[test]
+    fn test_join_path() {
+        assert_eq!("/api/users", join_path("/api", "/users"));
+        assert_eq!("/api/users", join_path("/api/", "users"));
+        assert_eq!("/users", join_path("", "/users"));
+    }
+
+    #[test]
+    fn test_group() {
+        let mut router = Router::new();
+        router.add("GET", "/", view);
+        router.group("/api", |group| {
+            group.middleware(Arc::new(|_request: &mut Request| Flow::Continue));
+            group.add("GET", "/users", view);
+            group.group("/v2", |group| {
+                group.add("GET", "/users", view);
+            });
+        });
+
+        let paths = router.into_paths();
+        let names: Vec<&str> = paths.iter().map(|path| path.name.as_str()).collect();
+        assert_eq!(vec!["/", "/api/users", "/api/v2/users"], names);
+
+        // Group middleware is applied only to the grouped paths
+        assert_eq!(0, paths[0].middlewares.len());
+        assert_eq!(1, paths[1].middlewares.len());
+        assert_eq!(1, paths[2].middlewares.len());
+    }
+
+    #[test]
+    fn test_url_for() {
+        let mut users = Router::new();
+        users.add_named("user_detail", "GET", "/:id", view);
+        users.add_named("user_file", "GET", "/:id/files/:name", view);
+
+        let mut router = Router::new();
+        router.mount("/users", users);
+
+        assert_eq!(Some("/users/42".to_string()), router.url_for("user_detail", &[("id", "42")]));
+        assert_eq!(Some("/users/42/files/a%20b.txt".to_string()),
+                   router.url_for("user_file", &[("name", "a b.txt"), ("id", "42")]));
+
+        // Missing parameter value
+        assert_eq!(None, router.url_for("user_detail", &[]));
+    }
+
+    #[test]
+    fn test_url_for_unknown_name() {
+        let mut router = Router::new();
+        router.add_named("home", "GET", "/", view);
+        assert_eq!(Some("/".to_string()), router.url_for("home", &[]));
+        assert_eq!(None, router.url_for("unknown", &[]));
+    }
+
+    #[test]
+    fn test_match_template() {
+        let params = match_template("/users/:id/files/:name", "/users/42/files/a%20b.txt").unwrap();
+        assert_eq!("42", params["id"]);
+        assert_eq!("a b.txt", params["name"]);
+
+        assert_eq!(true, match_template("/users/", "/users/").is_some());
+        assert_eq!(true, match_template("/users/:id", "/users/").is_none());
+        assert_eq!(true, match_template("/users/:id", "/users/42/files").is_none());
+        assert_eq!(true, match_template("/users/:id", "/posts/42").is_none());
+    }
+
+    #[test]
+    fn test_match_route_found() {
+        let mut router = Router::new();
+        router.add("GET", "/users/:id", view).add_any("/about", view);
+
+        match router.match_route("get", "/users/42") {
+            MatchResult::Found(params) => assert_eq!("42", params["id"]),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        assert_eq!(true, matches!(router.match_route("DELETE", "/about"), MatchResult::Found(_)));
+    }
+
+    #[test]
+    fn test_match_route_method_not_allowed() {
+        let mut router = Router::new();
+        router.add("POST", "/users/:id", view).add("GET", "/users/:id", view).add("PUT", "/users", view);
+
+        let expected = MatchResult::MethodNotAllowed(vec!["GET".to_string(), "HEAD".to_string(), "POST".to_string()]);
+        assert_eq!(expected, router.match_route("DELETE", "/users/42"));
+    }
+
+    #[test]
+    fn test_match_route_head() {
+        let mut router = Router::new();
+        router.add("GET", "/users/:id", view).add("POST", "/posts", view);
+
+        match router.match_route("HEAD", "/users/42") {
+            MatchResult::Found(params) => assert_eq!("42", params["id"]),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        let expected = MatchResult::MethodNotAllowed(vec!["POST".to_string()]);
+        assert_eq!(expected, router.match_route("HEAD", "/posts"));
+    }
+
+    #[test]
+    fn test_match_route_not_found() {
+        let mut router = Router::new();
+        router.add("GET", "/users/:id", view);
+
+        assert_eq!(MatchResult::NotFound, router.match_route("GET", "/users"));
+        assert_eq!(MatchResult::NotFound, router.match_route("GET", "/users/42/files"));
+        assert_eq!(MatchResult::NotFound, Router::new().match_route("GET", "/"));
+    }
+
+    #[test]
+    fn test_server_uses_match_result() {
+        let mut router = Router::new();
+        router.add("GET", "/users/:id", ok_view).add("PUT", "/users/:id", ok_view);
+        let paths = router.into_paths();
+
+        let raw_request = b"GET /users/42 HTTP/1.1\r\nConnection: keep-alive\r\n\r\n\
+                            DELETE /users/42 HTTP/1.1\r\nConnection: keep-alive\r\n\r\n\
+                            GET /posts HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let response = String::from_utf8(handle_raw(raw_request, paths)).unwrap();
+
+        let (found, rest) = response.split_once("HTTP/1.1 405 Method Not Allowed\r\n").unwrap();
+        assert_eq!(true, found.starts_with("HTTP/1.1 200 OK\r\n"));
+        let (not_allowed, not_found) = rest.split_once("HTTP/1.1 404 Not Found\r\n").unwrap();
+        assert_eq!(true, not_allowed.contains("Allow: GET, HEAD, PUT\r\n"));
+        assert_eq!(false, not_found.contains("Allow"));
+    }
+
+    #[test]
+    fn test_server_head_on_get_route() {
+        let mut router = Router::new();
+        router.add("GET", "/users/:id", ok_view);
+
+        let raw_request = b"HEAD /users/42 HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let response = String::from_utf8(handle_raw(raw_request, router.into_paths())).unwrap();
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.contains("\r\nContent-Length: 2\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\n"));
+    }
+}
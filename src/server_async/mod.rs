@@ -0,0 +1,530 @@
+//! Async server built on tokio. It is available with `async` feature.
+//!
+//! Unlike the blocking server, the whole request body is read asynchronously to memory before
+//! calling the view, so the existing body parsers are reused without blocking the runtime. The
+//! parsers receive the body as already read bytes. Because of this design:
+//!
+//! - Body size is limited by `AsyncConfig::max_body_size` and larger requests get
+//!   `413 Payload Too Large`.
+//! - Request with `Transfer-Encoding` gets `501 Not Implemented` since its length is unknown
+//!   before reading it.
+//! - Files of the multipart body are not available.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::headers;
+use crate::headers::Headers;
+use crate::parser::{multipart, parse_url_encoded};
+use crate::parser::multipart::{MultipartFormDataError, StreamReader};
+use crate::request::form::{FormData, MapFirstStringMethod};
+use crate::request::QueryParams;
+use crate::router::{resolve_path, MatchResult, Route};
+use crate::server::next_accept_backoff;
+use crate::status::{Status, StatusCode, StatusMethods};
+
+pub type AsyncViewFuture = Pin<Box<dyn Future<Output=AsyncResponse> + Send>>;
+pub type AsyncView = Arc<dyn Fn(AsyncRequest) -> AsyncViewFuture + Send + Sync>;
+pub type AsyncPaths = Vec<AsyncPath>;
+
+/// Path served by the async view
+pub struct AsyncPath {
+    pub name: String,
+    /// Request method served by this path. If it is `None`, all the methods are served.
+    pub method: Option<String>,
+    pub view: AsyncView,
+}
+
+impl AsyncPath {
+    pub fn new<F, Fut>(name: &str, view: F) -> Self
+        where F: Fn(AsyncRequest) -> Fut + Send + Sync + 'static,
+              Fut: Future<Output=AsyncResponse> + Send + 'static {
+        return Self {
+            name: name.to_string(),
+            method: None,
+            view: Arc::new(move |request| Box::pin(view(request))),
+        };
+    }
+
+    /// Path which only serves the given request method
+    pub fn with_method<F, Fut>(method: &str, name: &str, view: F) -> Self
+        where F: Fn(AsyncRequest) -> Fut + Send + Sync + 'static,
+              Fut: Future<Output=AsyncResponse> + Send + 'static {
+        let mut path = Self::new(name, view);
+        path.method = Some(method.to_uppercase());
+        return path;
+    }
+}
+
+impl Route for AsyncPath {
+    fn template(&self) -> &str {
+        return &self.name;
+    }
+
+    fn method(&self) -> Option<&str> {
+        return self.method.as_deref();
+    }
+}
+
+/// Limits of the async server. The request body is read to memory, so `max_body_size` also limits
+/// the memory used by each request.
+pub struct AsyncConfig {
+    /// Maximum size of the request line and headers. Larger requests are responded with
+    /// `431 Request Header Fields Too Large`.
+    pub max_header_size: usize,
+    /// Maximum size of the request body. Larger requests are responded with
+    /// `413 Payload Too Large`.
+    pub max_body_size: usize,
+}
+
+impl Default for AsyncConfig {
+    fn default() -> Self {
+        return Self {
+            max_header_size: 1024 * 1024, // 1 MiB
+            max_body_size: 16 * 1024 * 1024, // 16 MiB
+        };
+    }
+}
+
+/// Request passed to the async view. The body is already read to memory and limited by
+/// `AsyncConfig::max_body_size`.
+pub struct AsyncRequest {
+    pub method: String,
+    pub raw_path: String,
+    pub pathname: String,
+    pub query_params: QueryParams,
+    /// Values of the `:name` segments of the matched route
+    pub path_params: HashMap<String, String>,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl AsyncRequest {
+    pub fn path_param(&self, name: &str) -> Option<&str> {
+        return self.path_params.get(name).map(|value| value.as_str());
+    }
+
+    /// Parses `application/x-www-form-urlencoded` or `multipart/form-data` body. Files of the
+    /// multipart body are ignored.
+    pub fn form_data(&self) -> Result<FormData, MultipartFormDataError> {
+        let content_type = headers::extract_content_type(&self.headers).unwrap_or_default();
+
+        if content_type.starts_with("application/x-www-form-urlencoded") {
+            return Ok(parse_url_encoded(&String::from_utf8_lossy(&self.body)));
+        }
+
+        if !content_type.starts_with("multipart/form-data") {
+            return Ok(FormData::new());
+        }
+
+        // Body is already limited while reading it to memory
+        let form_parts = multipart::parse(self.body.clone(), &self.headers, MemoryReader, multipart::Limits::none())?;
+
+        let mut form_data = FormData::new();
+        for form_part in form_parts {
+            if let (Some(name), Some(value)) = (form_part.name, form_part.value) {
+                let value = String::from_utf8_lossy(&value).to_string();
+                form_data.entry(name).or_default().push(value);
+            }
+        }
+
+        return Ok(form_data);
+    }
+
+    /// Returns the first value of the form field
+    pub fn form_value(&self, name: &str) -> Option<String> {
+        return match self.form_data() {
+            Ok(form_data) => form_data.value(name),
+            Err(_) => None,
+        };
+    }
+}
+
+/// Reader passed to the parsers along with the whole body as already read bytes. There is nothing
+/// more to read.
+struct MemoryReader;
+
+impl StreamReader for MemoryReader {
+    type Error = MultipartFormDataError;
+
+    fn get_chunk(&mut self) -> Result<Vec<u8>, MultipartFormDataError> {
+        return Err(MultipartFormDataError::BodyReadEnd);
+    }
+
+    fn get_exact(&mut self, _size: usize) -> Result<Vec<u8>, MultipartFormDataError> {
+        return Err(MultipartFormDataError::BodyReadEnd);
+    }
+}
+
+/// Response returned by the async view. It is sent by the server.
+pub struct AsyncResponse {
+    pub status: usize,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl AsyncResponse {
+    pub fn new<T: StatusCode>(status: T, body: Vec<u8>) -> Self {
+        return Self {
+            status: status.to_usize(),
+            headers: HashMap::new(),
+            body,
+        };
+    }
+
+    pub fn html<T: StatusCode>(status: T, text: String) -> Self {
+        let mut response = Self::new(status, text.into_bytes());
+        response.set_header("Content-Type", "text/html");
+        return response;
+    }
+
+    pub fn json<T: StatusCode>(status: T, text: String) -> Self {
+        let mut response = Self::new(status, text.into_bytes());
+        response.set_header("Content-Type", "application/json");
+        return response;
+    }
+
+    /// Replaces all the existing values of the header with the given value
+    pub fn set_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.headers.insert(name.to_string(), vec![value.to_string()]);
+        return self;
+    }
+
+    fn error(status: usize) -> Self {
+        let status_text = Status::status_text(status).unwrap_or("Custom Status".to_string());
+        return Self::html(status, format!("{} {}", status, status_text.to_uppercase()));
+    }
+
+    fn to_bytes(&self, method: &str, keep_alive: bool) -> Vec<u8> {
+        let status_text = Status::status_text(self.status).unwrap_or("Custom Status".to_string());
+        let mut raw_headers = format!("HTTP/1.1 {} {}\r\n", self.status, status_text);
+        // Informational, `204 No Content` and `304 Not Modified` responses never have a body
+        let has_body = !matches!(self.status, 100..=199 | 204 | 304);
+
+        for (name, values) in &self.headers {
+            // Content-Length is always the actual body length
+            if name.eq_ignore_ascii_case("Content-Length") {
+                continue;
+            }
+
+            for value in values {
+                raw_headers.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+
+        if has_body {
+            raw_headers.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        let connection_type = if keep_alive { "keep-alive" } else { "close" };
+        raw_headers.push_str(&format!("Connection: {}\r\n", connection_type));
+        raw_headers.push_str("\r\n");
+
+        let mut bytes = raw_headers.into_bytes();
+        if has_body && method != "HEAD" {
+            bytes.extend(&self.body);
+        }
+
+        return bytes;
+    }
+}
+
+/// Example usage
+/// ```no_run
+/// use rusty_web::server_async::{AsyncPath, AsyncPaths, AsyncRequest, AsyncResponse, run_server_async};
+/// use rusty_web::status::Status;
+///
+/// async fn home(_request: AsyncRequest) -> AsyncResponse {
+///     return AsyncResponse::html(Status::Ok, "Home Page".to_string());
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let paths: AsyncPaths = vec![
+///         AsyncPath::new("/", home),
+///     ];
+///
+///     run_server_async("0.0.0.0:8080", paths).await.unwrap();
+/// }
+/// ```
+pub async fn run_server_async(listen_address: &str, paths: AsyncPaths) -> std::io::Result<()> {
+    let listener = TcpListener::bind(listen_address).await?;
+    println!("Running server in: http://{}", listen_address);
+    serve_async(listener, paths).await;
+    return Ok(());
+}
+
+/// Serves the paths using a listener which is already bound by the caller
+pub async fn serve_async(listener: TcpListener, paths: AsyncPaths) {
+    serve_async_with_config(listener, paths, AsyncConfig::default()).await;
+}
+
+/// Same as `serve_async` but with custom limits
+pub async fn serve_async_with_config(listener: TcpListener, paths: AsyncPaths, config: AsyncConfig) {
+    let paths = Arc::new(paths);
+    let config = Arc::new(config);
+    let mut accept_backoff = Duration::ZERO;
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                accept_backoff = Duration::ZERO;
+                let paths = Arc::clone(&paths);
+                let config = Arc::clone(&config);
+                tokio::spawn(async move {
+                    serve_client(stream, paths, config).await;
+                });
+            }
+
+            Err(error) => {
+                accept_backoff = next_accept_backoff(&error, accept_backoff);
+                if !accept_backoff.is_zero() {
+                    eprintln!("Error accepting connection: {}. Retrying in {:?}", error, accept_backoff);
+                    tokio::time::sleep(accept_backoff).await;
+                }
+            }
+        }
+    }
+}
+
+async fn serve_client(mut stream: TcpStream, paths: Arc<AsyncPaths>, config: Arc<AsyncConfig>) {
+    // Bytes read after the previous request
+    let mut buffer = Vec::new();
+
+    loop {
+        let (request, keep_alive) = match read_request(&mut stream, &mut buffer, &config).await {
+            Ok(result) => result,
+            Err(Some(status)) => {
+                let response = AsyncResponse::error(status);
+                let _ = stream.write_all(&response.to_bytes("GET", false)).await;
+                return;
+            }
+            Err(None) => {
+                return;
+            }
+        };
+
+        let method = request.method.clone();
+        let response = serve_request(request, &paths).await;
+
+        if stream.write_all(&response.to_bytes(&method, keep_alive)).await.is_err() {
+            return;
+        }
+
+        if !keep_alive {
+            let _ = stream.shutdown().await;
+            return;
+        }
+    }
+}
+
+async fn serve_request(mut request: AsyncRequest, paths: &AsyncPaths) -> AsyncResponse {
+    let (matched_path, match_result) = resolve_path(paths, &request.method, &request.pathname);
+
+    return match (matched_path, match_result) {
+        (Some(path), MatchResult::Found(params)) => {
+            request.path_params = params;
+            (path.view)(request).await
+        }
+
+        (_, MatchResult::MethodNotAllowed(methods)) => {
+            let mut response = AsyncResponse::error(Status::MethodNotAllowed.to_usize());
+            response.set_header("Allow", &methods.join(", "));
+            response
+        }
+
+        _ => AsyncResponse::error(Status::NotFound.to_usize()),
+    };
+}
+
+/// Reads the next request from the stream. Returns the error status code which should be sent
+/// before closing the connection, or `None` if the client is disconnected.
+async fn read_request(stream: &mut TcpStream, buffer: &mut Vec<u8>, config: &AsyncConfig)
+                      -> Result<(AsyncRequest, bool), Option<usize>> {
+    let header_end = loop {
+        if let Some(header_end) = headers::contains_full_headers(buffer) {
+            break header_end;
+        }
+
+        if buffer.len() > config.max_header_size {
+            return Err(Some(Status::RequestHeaderFieldsTooLarge.to_usize()));
+        }
+
+        read_more(stream, buffer).await.map_err(|_| None)?;
+    };
+
+    let raw_headers = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    buffer.drain(..header_end + 4);
+
+    let mut lines = raw_headers.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let (method, raw_path) = match headers::parse_request_method_header(request_line) {
+        Some(request_info) => request_info,
+        None => {
+            return Err(Some(Status::BadRequest.to_usize()));
+        }
+    };
+
+    let mut request_headers: Headers = HashMap::new();
+    for line in lines {
+        if let Some((name, value)) = headers::parse_header(line) {
+            request_headers.entry(name).or_default().push(value);
+        }
+    }
+
+    if request_headers.contains_key("Transfer-Encoding") {
+        return Err(Some(Status::NotImplemented.to_usize()));
+    }
+
+    let content_length = match request_headers.get("Content-Length").and_then(|values| values.first()) {
+        Some(value) => match value.parse::<usize>() {
+            Ok(content_length) => content_length,
+            Err(_) => {
+                return Err(Some(Status::BadRequest.to_usize()));
+            }
+        },
+        None => 0,
+    };
+
+    if content_length > config.max_body_size {
+        return Err(Some(Status::PayloadTooLarge.to_usize()));
+    }
+
+    while buffer.len() < content_length {
+        read_more(stream, buffer).await.map_err(|_| None)?;
+    }
+
+    // Remaining bytes belong to the next pipelined request
+    let body: Vec<u8> = buffer.drain(..content_length).collect();
+
+    let keep_alive = headers::is_keep_alive(&request_headers);
+
+    let pathname = match raw_path.split_once("?") {
+        Some((pathname, _)) => pathname.to_string(),
+        None => raw_path.clone(),
+    };
+
+    let request = AsyncRequest {
+        method,
+        query_params: headers::query_params_from_raw(&raw_path),
+        raw_path,
+        pathname,
+        path_params: HashMap::new(),
+        headers: request_headers,
+        body,
+    };
+
+    return Ok((request, keep_alive));
+}
+
+/// Reads the next chunk from the stream. Returns error if the client is disconnected.
+async fn read_more(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> std::io::Result<()> {
+    let mut chunk = [0u8; 8192];
+    let read_size = stream.read(&mut chunk).await?;
+
+    if read_size == 0 {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+
+    buffer.extend(&chunk[..read_size]);
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use crate::server_async::{AsyncConfig, AsyncPath, AsyncPaths, AsyncRequest, AsyncResponse, serve_async,
+                              serve_async_with_config};
+    use crate::status::Status;
+
+    async fn greet(request: AsyncRequest) -> AsyncResponse {
+        let name = request.path_param("name").unwrap_or_default().to_string();
+        return AsyncResponse::html(Status::Ok, format!("Hello {}", name));
+    }
+
+    async fn submit(request: AsyncRequest) -> AsyncResponse {
+        let name = request.form_value("name").unwrap_or_default();
+        return AsyncResponse::json(Status::Created, format!("{{\"name\": \"{}\"}}", name));
+    }
+
+    async fn send_raw(address: &str, raw_request: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(raw_request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        return response;
+    }
+
+    #[tokio::test]
+    async fn test_async_server() {
+        let paths: AsyncPaths = vec![
+            AsyncPath::with_method("GET", "/hello/:name", greet),
+            AsyncPath::with_method("POST", "/submit", submit),
+        ];
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve_async(listener, paths));
+
+        let response = send_raw(&address, "GET /hello/John HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.ends_with("Hello John"));
+
+        let raw_request = "POST /submit HTTP/1.1\r\nConnection: keep-alive\r\n\
+                           Content-Type: application/x-www-form-urlencoded\r\nContent-Length: 9\r\n\r\nname=Jane\
+                           GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let response = send_raw(&address, raw_request).await;
+        assert_eq!(true, response.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert_eq!(true, response.contains("{\"name\": \"Jane\"}HTTP/1.1 404 Not Found\r\n"));
+
+        let response = send_raw(&address, "POST /hello/John HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+        assert_eq!(true, response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert_eq!(true, response.contains("Allow: GET, HEAD\r\n"));
+
+        let response = send_raw(&address, "HEAD /hello/John HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.contains("Content-Length: 10\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_async_body_limit() {
+        let paths: AsyncPaths = vec![
+            AsyncPath::with_method("POST", "/submit", submit),
+        ];
+
+        let config = AsyncConfig {
+            max_body_size: 4,
+            ..AsyncConfig::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve_async_with_config(listener, paths, config));
+
+        let raw_request = "POST /submit HTTP/1.1\r\nContent-Length: 9\r\n\r\nname=Jane";
+        let response = send_raw(&address, raw_request).await;
+        assert_eq!(true, response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+
+    #[test]
+    fn test_response_to_bytes() {
+        let mut response = AsyncResponse::html(Status::Ok, "hi".to_string());
+        response.set_header("content-length", "99");
+        let raw_response = String::from_utf8(response.to_bytes("GET", true)).unwrap();
+        assert_eq!(1, raw_response.to_lowercase().matches("content-length").count());
+        assert_eq!(true, raw_response.contains("\r\nContent-Length: 2\r\n"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\nhi"));
+
+        let response = AsyncResponse::new(Status::NoContent, b"ignored".to_vec());
+        let raw_response = String::from_utf8(response.to_bytes("GET", true)).unwrap();
+        assert_eq!(true, raw_response.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert_eq!(false, raw_response.contains("Content-Length"));
+        assert_eq!(true, raw_response.ends_with("\r\n\r\n"));
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::{Display, Formatter};
+
 /// Refer to this url for more information: https://developer.mozilla.org/en-US/docs/Web/HTTP/Status
 #[derive(Debug)]
 pub enum Status {
@@ -79,6 +81,94 @@ pub enum Status {
     NetworkAuthenticationRequired = 511,
 }
 
+impl Status {
+    /// Returns the matching `Status` for the given status code. Returns `None` if the status code
+    /// is not known even if it is in the valid range.
+    pub fn from_u16(code: u16) -> Option<Status> {
+        return match code {
+            // Information responses
+            100 => Some(Status::Continue),
+            101 => Some(Status::SwitchingProtocols),
+            102 => Some(Status::Processing),
+            103 => Some(Status::EarlyHints),
+
+            // Successful responses
+            200 => Some(Status::Ok),
+            201 => Some(Status::Created),
+            202 => Some(Status::Accepted),
+            203 => Some(Status::NonAuthoritativeInformation),
+            204 => Some(Status::NoContent),
+            205 => Some(Status::ResetContent),
+            206 => Some(Status::PartialContent),
+            207 => Some(Status::MultiStatus),
+            208 => Some(Status::AlreadyReported),
+            226 => Some(Status::ImUsed),
+
+            // Redirection Messages
+            300 => Some(Status::MultipleChoices),
+            301 => Some(Status::MovedPermanently),
+            302 => Some(Status::Found),
+            303 => Some(Status::SeeOther),
+            304 => Some(Status::NotModified),
+            305 => Some(Status::UseProxy),
+            306 => Some(Status::UnUsed),
+            307 => Some(Status::TemporaryRedirect),
+            308 => Some(Status::PermanentRedirect),
+
+            // Client error responses
+            400 => Some(Status::BadRequest),
+            401 => Some(Status::UnAuthorized),
+            402 => Some(Status::PaymentRequired),
+            403 => Some(Status::Forbidden),
+            404 => Some(Status::NotFound),
+            405 => Some(Status::MethodNotAllowed),
+            406 => Some(Status::NotAcceptable),
+            407 => Some(Status::ProxyAuthenticationRequired),
+            408 => Some(Status::RequestTimeout),
+            409 => Some(Status::Conflict),
+            410 => Some(Status::Gone),
+            411 => Some(Status::LengthRequired),
+            412 => Some(Status::PreconditionFailed),
+            413 => Some(Status::PayloadTooLarge),
+            414 => Some(Status::UriTooLong),
+            415 => Some(Status::UnsupportedMediaType),
+            416 => Some(Status::RangeNotSatisfiable),
+            417 => Some(Status::ExpectationFailed),
+            418 => Some(Status::ImaTeaPot),
+            421 => Some(Status::MisRedirectRequest),
+            422 => Some(Status::UnprocessableContent),
+            423 => Some(Status::Locked),
+            424 => Some(Status::FailedDependency),
+            425 => Some(Status::TooEarly),
+            426 => Some(Status::UpgradeRequired),
+            428 => Some(Status::PreconditionRequired),
+            429 => Some(Status::TooManyRequests),
+            431 => Some(Status::RequestHeaderFieldsTooLarge),
+            451 => Some(Status::UnavailableForLegalReasons),
+
+            // Server error responses
+            500 => Some(Status::InternalServerError),
+            501 => Some(Status::NotImplemented),
+            502 => Some(Status::BadGateway),
+            503 => Some(Status::ServiceUnavailable),
+            504 => Some(Status::GatewayTimeout),
+            505 => Some(Status::HttpVersionNotSupported),
+            506 => Some(Status::VariantAlsoNegotiates),
+            507 => Some(Status::InsufficientStorage),
+            508 => Some(Status::LoopDetected),
+            510 => Some(Status::NotExtended),
+            511 => Some(Status::NetworkAuthenticationRequired),
+            _ => None
+        };
+    }
+}
+
+/// Returns true if the status code is in the valid range of 100 to 599.
+/// The status code does not need to be known by `Status`.
+pub fn is_valid_status_code(status_code: usize) -> bool {
+    return (100..=599).contains(&status_code);
+}
+
 pub trait StatusMethods {
     fn status_code(&self) -> Option<usize>;
     fn status_text(status_code: usize) -> Option<String>;
@@ -178,7 +268,7 @@ impl StatusMethods for Status {
             204 => Some("No Content".to_string()),
             205 => Some("Reset Content".to_string()),
             206 => Some("Partial Content".to_string()),
-            207 => Some("Multi_Status".to_string()),
+            207 => Some("Multi-Status".to_string()),
             208 => Some("Already Reported".to_string()),
             226 => Some("IM Used".to_string()),
 
@@ -212,7 +302,7 @@ impl StatusMethods for Status {
             415 => Some("Unsupported Media Type".to_string()),
             416 => Some("Range Not Satisfiable".to_string()),
             417 => Some("Expectation Failed".to_string()),
-            418 => Some("I',m a teapot".to_string()),
+            418 => Some("I'm a teapot".to_string()),
             421 => Some("Misdirected Request".to_string()),
             422 => Some("Unprocessable Content".to_string()),
             423 => Some("Locked".to_string()),
@@ -241,6 +331,19 @@ impl StatusMethods for Status {
     }
 }
 
+/// Formats the reason phrase of the status. Example: `Not Found`
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status_text = Status::status_text(self.to_usize()).expect("Status text missing");
+        return write!(f, "{}", status_text);
+    }
+}
+
+impl From<Status> for u16 {
+    fn from(status: Status) -> Self {
+        return status.to_usize() as u16;
+    }
+}
 
 pub trait StatusCode {
     fn to_usize(&self) -> usize;
@@ -257,4 +360,39 @@ impl StatusCode for usize {
     fn to_usize(&self) -> usize {
         *self
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use crate::status::{is_valid_status_code, Status, StatusCode};
+
+    #[test]
+    fn test_from_u16() {
+        assert_eq!(404, Status::from_u16(404).unwrap().to_usize());
+        assert_eq!(200, Status::from_u16(200).unwrap().to_usize());
+        assert_eq!(true, Status::from_u16(299).is_none());
+        assert_eq!(true, Status::from_u16(999).is_none());
+    }
+
+    #[test]
+    fn test_is_valid_status_code() {
+        assert_eq!(true, is_valid_status_code(200));
+        assert_eq!(true, is_valid_status_code(299));
+        assert_eq!(false, is_valid_status_code(99));
+        assert_eq!(false, is_valid_status_code(999));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("OK", format!("{}", Status::Ok));
+        assert_eq!("Not Found", format!("{}", Status::NotFound));
+        assert_eq!("Internal Server Error", Status::InternalServerError.to_string());
+    }
+
+    #[test]
+    fn test_into_u16() {
+        let status_code: u16 = Status::NotFound.into();
+        assert_eq!(404, status_code);
+        assert_eq!(201, u16::from(Status::Created));
+    }
+}
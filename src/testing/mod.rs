@@ -0,0 +1,105 @@
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, RwLock};
+use std::thread::spawn;
+use crate::paths::Paths;
+use crate::server::{serve_client, Config, Context};
+
+/// Runs the raw request through the full server pipeline and returns the raw response bytes.
+/// The harness is backed by a TCP connection over the loopback interface instead of an in-memory
+/// stream, but no server needs to be started. Request is written while the response is read, so
+/// large requests and responses don't block each other.
+///
+/// ```rust
+/// use rusty_web::paths::{Path, Paths};
+/// use rusty_web::request::Request;
+/// use rusty_web::response::Response;
+/// use rusty_web::status::Status;
+/// use rusty_web::testing::handle_raw;
+///
+/// fn home(request: Request, mut response: Response) {
+///    response.html(Status::Ok, "Home Page".to_string()).send();
+/// }
+///
+/// let paths: Paths = vec![Path::new("/", home)];
+/// let response = handle_raw(b"GET / HTTP/1.1\r\n\r\n", paths);
+/// assert_eq!(true, response.starts_with(b"HTTP/1.1 200 OK"));
+/// ```
+pub fn handle_raw(request_bytes: &[u8], paths: Paths) -> Vec<u8> {
+    return handle_raw_with_config(request_bytes, paths, Config::default());
+}
+
+/// Same as `handle_raw` but uses the given server configuration
+pub fn handle_raw_with_config(request_bytes: &[u8], paths: Paths, config: Config) -> Vec<u8> {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Error binding loopback listener");
+    let address = listener.local_addr().expect("Error reading loopback address");
+
+    let mut client = TcpStream::connect(address).expect("Error connecting loopback listener");
+    let (stream, _) = listener.accept().expect("Error accepting loopback connection");
+
+    let paths = Arc::new(RwLock::new(paths));
+    let context = Arc::new(Context::new(Arc::new(config)));
+    let server = spawn(move || {
+        serve_client(stream, paths, context);
+    });
+
+    let mut writer = client.try_clone().expect("Error cloning loopback connection");
+    let request_bytes = request_bytes.to_vec();
+    let request_writer = spawn(move || {
+        // Server may close the connection before reading the whole request
+        let _ = writer.write_all(&request_bytes);
+        // No more requests are sent, so the server closes the connection after responding
+        let _ = writer.shutdown(Shutdown::Write);
+    });
+
+    let mut response_bytes = Vec::new();
+    let _ = client.read_to_end(&mut response_bytes);
+    let _ = request_writer.join();
+    let _ = server.join();
+    return response_bytes;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::paths::{Path, Paths};
+    use crate::request::Request;
+    use crate::response::Response;
+    use crate::status::Status;
+    use crate::testing::handle_raw;
+
+    fn home(_request: Request, mut response: Response) {
+        response.html(Status::Ok, "Home Page".to_string()).send();
+    }
+
+    #[test]
+    fn test_handle_raw() {
+        let paths: Paths = vec![Path::with_method("GET", "/", home)];
+        let response = handle_raw(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n", paths);
+        let response = String::from_utf8(response).unwrap();
+
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(true, response.ends_with("\r\n\r\nHome Page"));
+
+        let paths: Paths = vec![Path::with_method("GET", "/", home)];
+        let response = handle_raw(b"GET /missing HTTP/1.1\r\n\r\n", paths);
+        assert_eq!(true, response.starts_with(b"HTTP/1.1 404"));
+    }
+
+    fn large(mut request: Request, mut response: Response) {
+        let _ = request.body();
+        response.bytes(Status::Ok, vec![b'a'; 8 * 1024 * 1024]).send();
+    }
+
+    #[test]
+    fn test_handle_raw_large() {
+        // Response of the first request is sent while the large body of the next one is written
+        let mut raw_request = b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n".to_vec();
+        raw_request.extend(b"POST / HTTP/1.1\r\nContent-Length: 8388608\r\nConnection: keep-alive\r\n\r\n");
+        raw_request.extend(vec![b'b'; 8 * 1024 * 1024]);
+
+        let paths: Paths = vec![Path::new("/", large)];
+        let response = handle_raw(&raw_request, paths);
+        assert_eq!(2, response.windows(15).filter(|window| *window == b"HTTP/1.1 200 OK").count());
+        assert_eq!(true, response.len() > 16 * 1024 * 1024);
+    }
+}